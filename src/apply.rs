@@ -0,0 +1,545 @@
+use clap;
+use confirm::{confirm, describe_collection, describe_configuration};
+use diff::{Difference, diff, print_differences, without_server_fields};
+use info::{EnvironmentInfo, discovery_service_info, environment_info};
+use request;
+use select::configuration_with_id;
+use serde_json::{Value, to_string_pretty};
+use size::{Size, create_with_size, parse_size};
+use std;
+use std::collections::BTreeMap;
+use std::path::Path;
+use template::{read_document, substitute};
+use validate::validate_or_exit;
+use wait::{Poll, wait_for_status};
+use wdsapi::collection;
+use wdsapi::collection::NewCollection;
+use wdsapi::common::Credentials;
+use wdsapi::configuration;
+use wdsapi::environment;
+
+// Every environment starts with this configuration and it cannot be
+// deleted, so it is never part of a plan.
+pub const DEFAULT_CONFIGURATION: &str = "Default Configuration";
+
+// What a manifest asks for. Configurations carry their name, whether they
+// were given inline or read from a file.
+struct Manifest {
+    environment: Value,
+    configurations: Vec<Value>,
+    collections: Vec<Value>,
+}
+
+enum Action {
+    CreateEnvironment,
+    CreateConfiguration(Value),
+    UpdateConfiguration(Value, Value, Vec<Difference>),
+    CreateCollection(Value),
+    UpdateCollection(Value, Value),
+    DeleteCollection(Value),
+    DeleteConfiguration(Value),
+}
+
+fn name_of(value: &Value) -> &str {
+    value["name"].as_str().unwrap_or("")
+}
+
+fn manifest_list(manifest: &Value, key: &str) -> Vec<Value> {
+    match manifest[key] {
+        Value::Null => Vec::new(),
+        Value::Array(ref items) => items.clone(),
+        _ => {
+            println!("Expected a list of {} in the manifest", key);
+            std::process::exit(1)
+        }
+    }
+}
+
+// A configuration entry has a name and either a "file", read relative to
+// the manifest, or the configuration itself under "configuration".
+fn manifest_configuration(entry: &Value, base: &Path) -> Value {
+    let name = match entry["name"].as_str() {
+        Some(name) => name,
+        None => {
+            println!("Every configuration in the manifest needs a name");
+            std::process::exit(1)
+        }
+    };
+    let mut config = if let Some(file) = entry["file"].as_str() {
+        let path = base.join(file);
+        read_document(path.to_str().expect("File name is not valid UTF-8"))
+    } else if entry["configuration"].is_object() {
+        entry["configuration"].clone()
+    } else {
+        println!(
+            "Configuration {} in the manifest needs a file or a \
+             configuration",
+            name
+        );
+        std::process::exit(1)
+    };
+    substitute(&mut config);
+    config["name"] = Value::String(name.to_string());
+    without_server_fields(&config)
+}
+
+// apply matches what exists by name, so two manifest entries with one name
+// would create a duplicate that later commands cannot select by name.
+fn check_unique_names(what: &str, items: &[Value]) {
+    for (index, item) in items.iter().enumerate() {
+        if items[..index].iter().any(|c| name_of(c) == name_of(item)) {
+            println!(
+                "The manifest names more than one {} {}",
+                what,
+                item["name"]
+            );
+            std::process::exit(1)
+        }
+    }
+}
+
+fn read_manifest(filename: &str) -> Manifest {
+    let manifest = read_document(filename);
+    let base = Path::new(filename).parent().unwrap_or(Path::new("."));
+    let mut environment = manifest["environment"].clone();
+    substitute(&mut environment);
+    if environment["name"].as_str().is_none() {
+        println!("The manifest needs an environment with a name");
+        std::process::exit(1)
+    }
+    let mut collections = manifest_list(&manifest, "collections");
+    for collection in collections.iter_mut() {
+        substitute(collection);
+        if collection["name"].as_str().is_none() {
+            println!("Every collection in the manifest needs a name");
+            std::process::exit(1)
+        }
+    }
+    let configurations: Vec<Value> =
+        manifest_list(&manifest, "configurations")
+            .iter()
+            .map(|entry| manifest_configuration(entry, base))
+            .collect();
+    check_unique_names("configuration", &configurations);
+    check_unique_names("collection", &collections);
+    Manifest {
+        environment: environment,
+        configurations: configurations,
+        collections: collections,
+    }
+}
+
+// The size may be a name like XS or a number.
+fn environment_size(environment: &Value) -> Size {
+    match environment["size"] {
+        Value::Null => Size::Number(0),
+        Value::String(ref size) => parse_size(size),
+        ref size => parse_size(&size.to_string()),
+    }
+}
+
+fn configuration_detail(
+    creds: &Credentials,
+    env_id: &str,
+    existing: &Value,
+) -> Value {
+    let configuration_id = existing["configuration_id"].as_str().expect(
+        "Internal error: missing configuration_id",
+    );
+    match configuration::detail(creds, env_id, configuration_id) {
+        Ok(response) => without_server_fields(&response),
+        Err(e) => {
+            println!("Failed to lookup configuration {}", e);
+            std::process::exit(1)
+        }
+    }
+}
+
+// Compare the manifest with the writable environment, if there is one.
+// Actions are in the order they must be carried out.
+fn plan_actions(
+    creds: &Credentials,
+    manifest: &Manifest,
+    env_info: &Option<EnvironmentInfo>,
+    matches: &clap::ArgMatches,
+) -> Vec<Action> {
+    let mut actions = Vec::new();
+    let no_env = EnvironmentInfo {
+        environment_id: String::new(),
+        environment: Value::Null,
+        configurations: Vec::new(),
+        collections: Vec::new(),
+    };
+    let env = match *env_info {
+        Some(ref env) => env,
+        None => {
+            actions.push(Action::CreateEnvironment);
+            &no_env
+        }
+    };
+
+    for desired in &manifest.configurations {
+        validate_or_exit(desired, matches);
+        let existing = env.configurations
+                          .iter()
+                          .find(|c| name_of(c) == name_of(desired));
+        match existing {
+            None => actions.push(Action::CreateConfiguration(desired.clone())),
+            Some(existing) => {
+                let current =
+                    configuration_detail(creds, &env.environment_id, existing);
+                let differences = diff(&current, desired);
+                if !differences.is_empty() {
+                    actions.push(Action::UpdateConfiguration(
+                        existing.clone(),
+                        desired.clone(),
+                        differences,
+                    ))
+                }
+            }
+        }
+    }
+
+    for desired in &manifest.collections {
+        if let Some(wanted) = desired["configuration"].as_str() {
+            // Every environment has the default configuration, even one
+            // that is yet to be created.
+            let known = wanted == DEFAULT_CONFIGURATION ||
+                manifest.configurations
+                        .iter()
+                        .chain(env.configurations.iter())
+                        .any(|c| name_of(c) == wanted);
+            if !known {
+                println!(
+                    "Collection {} uses configuration {}, which is neither \
+                     in the manifest nor in the environment",
+                    desired["name"],
+                    wanted
+                );
+                std::process::exit(1)
+            }
+        }
+        let existing = env.collections
+                          .iter()
+                          .find(|c| name_of(c) == name_of(desired));
+        match existing {
+            None => actions.push(Action::CreateCollection(desired.clone())),
+            Some(existing) => {
+                let current_configuration = configuration_with_id(
+                    env,
+                    existing["configuration_id"].as_str().unwrap_or(""),
+                );
+                let description_changed =
+                    !desired["description"].is_null() &&
+                        desired["description"] != existing["description"];
+                let configuration_changed =
+                    !desired["configuration"].is_null() &&
+                        desired["configuration"] !=
+                            current_configuration["name"];
+                if description_changed || configuration_changed {
+                    // Whatever the manifest leaves out stays as it is.
+                    let mut merged = desired.clone();
+                    if merged["description"].is_null() {
+                        merged["description"] = existing["description"].clone()
+                    }
+                    if merged["configuration"].is_null() {
+                        merged["configuration"] =
+                            current_configuration["name"].clone()
+                    }
+                    actions.push(
+                        Action::UpdateCollection(existing.clone(), merged),
+                    )
+                }
+            }
+        }
+    }
+
+    for existing in &env.collections {
+        if !manifest.collections.iter().any(
+            |c| name_of(c) == name_of(existing),
+        )
+        {
+            actions.push(Action::DeleteCollection(existing.clone()))
+        }
+    }
+    for existing in &env.configurations {
+        if name_of(existing) != DEFAULT_CONFIGURATION &&
+            !manifest.configurations.iter().any(
+                |c| name_of(c) == name_of(existing),
+            )
+        {
+            actions.push(Action::DeleteConfiguration(existing.clone()))
+        }
+    }
+    actions
+}
+
+// apply does not rename or resize an environment, so differences are
+// only reported.
+fn report_environment(manifest: &Manifest, env_info: &Option<EnvironmentInfo>) {
+    if let Some(ref env) = *env_info {
+        let wanted = &manifest.environment;
+        if name_of(&env.environment) != name_of(wanted) {
+            println!(
+                "Note: environment is named {}, not {}; apply leaves it as is",
+                env.environment["name"],
+                wanted["name"]
+            )
+        }
+        if !environment_size(wanted).is(&env.environment["size"]) {
+            println!(
+                "Note: environment size is {}, not {}; use resize-environment \
+                 to change it",
+                env.environment["size"],
+                environment_size(wanted)
+            )
+        }
+    }
+}
+
+fn print_plan(manifest: &Manifest, actions: &[Action]) {
+    for action in actions {
+        match *action {
+            Action::CreateEnvironment => {
+                println!(
+                    "+ create environment {}, size={}",
+                    manifest.environment["name"],
+                    environment_size(&manifest.environment)
+                )
+            }
+            Action::CreateConfiguration(ref desired) => {
+                println!("+ create configuration {}", desired["name"])
+            }
+            Action::UpdateConfiguration(ref existing, _, ref differences) => {
+                println!("~ update configuration {}", existing["name"]);
+                print_differences(differences);
+            }
+            Action::CreateCollection(ref desired) => {
+                println!(
+                    "+ create collection {} ↳ {}",
+                    desired["name"],
+                    desired["configuration"]
+                )
+            }
+            Action::UpdateCollection(ref existing, ref desired) => {
+                println!(
+                    "~ update collection {} ↳ {}",
+                    existing["name"],
+                    desired["configuration"]
+                )
+            }
+            Action::DeleteCollection(ref existing) => {
+                println!(
+                    "- delete collection {}",
+                    describe_collection(existing)
+                )
+            }
+            Action::DeleteConfiguration(ref existing) => {
+                println!(
+                    "- delete configuration {}",
+                    describe_configuration(existing)
+                )
+            }
+        }
+    }
+}
+
+fn writable(info_environments: &[EnvironmentInfo]) -> Option<EnvironmentInfo> {
+    info_environments
+        .iter()
+        .find(|env| !env.environment["read_only"].as_bool().unwrap_or(true))
+        .cloned()
+}
+
+pub fn plan(creds: Credentials, matches: &clap::ArgMatches) {
+    let manifest = read_manifest(matches.value_of("manifest").unwrap());
+    let info = discovery_service_info(creds);
+    let env_info = writable(&info.environments);
+    report_environment(&manifest, &env_info);
+    let actions = plan_actions(&info.creds, &manifest, &env_info, matches);
+    if actions.is_empty() {
+        println!("No changes");
+    } else {
+        print_plan(&manifest, &actions);
+    }
+}
+
+fn fail(what: &str, e: &dyn std::fmt::Display) -> ! {
+    println!("Failed to {} {}", what, e);
+    std::process::exit(1)
+}
+
+fn show_response(response: &Value) {
+    println!(
+        "{}",
+        to_string_pretty(response).expect(
+            "Internal error: failed to format response",
+        )
+    )
+}
+
+fn create_environment(
+    creds: &Credentials,
+    manifest: &Manifest,
+    matches: &clap::ArgMatches,
+) -> EnvironmentInfo {
+    let wanted = &manifest.environment;
+    let response = create_with_size(
+        creds,
+        name_of(wanted),
+        wanted["description"].as_str().map(|d| d.to_string()),
+        environment_size(wanted),
+    ).unwrap_or_else(|e| fail("create environment", &e));
+    show_response(&response);
+    let env_id = response["environment_id"].as_str().expect(
+        "Internal error: missing environment_id",
+    );
+    if !wait_for_status(
+        &mut Poll::unbounded(matches),
+        "Environment",
+        &["active", "available"],
+        || environment::detail(creds, env_id),
+    )
+    {
+        std::process::exit(1)
+    }
+    environment_info(creds, &response)
+}
+
+fn collection_options(
+    desired: &Value,
+    configuration_ids: &BTreeMap<String, String>,
+) -> NewCollection {
+    NewCollection {
+        name: name_of(desired).to_string(),
+        description: desired["description"].as_str().map(|d| d.to_string()),
+        configuration_id: desired["configuration"].as_str().and_then(|name| {
+            // Without an id the service uses the default configuration.
+            if name == DEFAULT_CONFIGURATION &&
+                !configuration_ids.contains_key(name)
+            {
+                return None;
+            }
+            Some(
+                configuration_ids
+                    .get(name)
+                    .expect("Internal error: unknown configuration")
+                    .clone(),
+            )
+        }),
+    }
+}
+
+fn add_configuration_ids(
+    configuration_ids: &mut BTreeMap<String, String>,
+    env: &EnvironmentInfo,
+) {
+    for existing in &env.configurations {
+        if let Some(id) = existing["configuration_id"].as_str() {
+            configuration_ids.insert(name_of(existing).to_string(),
+                                     id.to_string());
+        }
+    }
+}
+
+pub fn apply(creds: Credentials, matches: &clap::ArgMatches) {
+    let manifest = read_manifest(matches.value_of("manifest").unwrap());
+    let info = discovery_service_info(creds);
+    let creds = &info.creds;
+    let env_info = writable(&info.environments);
+    report_environment(&manifest, &env_info);
+    let actions = plan_actions(creds, &manifest, &env_info, matches);
+    if actions.is_empty() {
+        println!("No changes");
+        return;
+    }
+    print_plan(&manifest, &actions);
+    confirm(
+        matches,
+        "apply this plan",
+        &[format!("{} changes to environment {}",
+                  actions.len(),
+                  manifest.environment["name"])],
+    );
+
+    let mut env = env_info.clone();
+    let mut configuration_ids: BTreeMap<String, String> = BTreeMap::new();
+    if let Some(ref env) = env {
+        add_configuration_ids(&mut configuration_ids, env);
+    }
+    for action in &actions {
+        if let Action::CreateEnvironment = *action {
+            let created = create_environment(creds, &manifest, matches);
+            // A new environment comes with its default configuration.
+            add_configuration_ids(&mut configuration_ids, &created);
+            env = Some(created);
+            continue;
+        }
+        let env_id = &env.as_ref()
+                         .expect("Internal error: no environment")
+                         .environment_id;
+        match *action {
+            Action::CreateEnvironment => (),
+            Action::CreateConfiguration(ref desired) => {
+                let response = configuration::create(creds, env_id, desired)
+                    .unwrap_or_else(|e| fail("create configuration", &e));
+                show_response(&response);
+                configuration_ids.insert(
+                    name_of(desired).to_string(),
+                    response["configuration_id"]
+                        .as_str()
+                        .expect("Internal error: missing configuration_id")
+                        .to_string(),
+                );
+            }
+            Action::UpdateConfiguration(ref existing, ref desired, _) => {
+                let configuration_id = existing["configuration_id"]
+                    .as_str()
+                    .expect("Internal error: missing configuration_id");
+                let response = request::update_configuration(
+                    creds,
+                    env_id,
+                    configuration_id,
+                    desired,
+                ).unwrap_or_else(|e| fail("update configuration", &e));
+                show_response(&response);
+            }
+            Action::CreateCollection(ref desired) => {
+                let response = collection::create(
+                    creds,
+                    env_id,
+                    &collection_options(desired, &configuration_ids),
+                ).unwrap_or_else(|e| fail("create collection", &e));
+                show_response(&response);
+            }
+            Action::UpdateCollection(ref existing, ref desired) => {
+                let collection_id = existing["collection_id"].as_str().expect(
+                    "Internal error: missing collection_id",
+                );
+                let response = request::update_collection(
+                    creds,
+                    env_id,
+                    collection_id,
+                    &collection_options(desired, &configuration_ids),
+                ).unwrap_or_else(|e| fail("update collection", &e));
+                show_response(&response);
+            }
+            Action::DeleteCollection(ref existing) => {
+                let collection_id = existing["collection_id"].as_str().expect(
+                    "Internal error: missing collection_id",
+                );
+                collection::delete(creds, env_id, collection_id)
+                    .unwrap_or_else(|e| fail("delete collection", &e));
+                println!("Deleted collection {}", existing["name"]);
+            }
+            Action::DeleteConfiguration(ref existing) => {
+                let configuration_id = existing["configuration_id"]
+                    .as_str()
+                    .expect("Internal error: missing configuration_id");
+                configuration::delete(creds, env_id, configuration_id)
+                    .unwrap_or_else(|e| fail("delete configuration", &e));
+                println!("Deleted configuration {}", existing["name"]);
+            }
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use query::{RESULT_WINDOW, all_documents, each_result};
+use serde_json::{Value, from_reader, to_writer_pretty};
+use std::fs::{File, create_dir_all, read_dir};
+use std::path::{Path, PathBuf};
+use wdsapi::common::Credentials;
+use wdsapi::configuration;
+
+// A backup of a collection is a directory named for the collection_id,
+// holding:
+//   collection.json    the collection detail
+//   configuration.json the configuration used by the collection
+//   documents/         one <document_id>.json file per document
+// The documents directory works as input to `add-document --filename-ids`.
+
+// Query results carry some fields which are not part of the document.
+pub fn document_content(result: &Value) -> Value {
+    let mut content = result.clone();
+    if let Some(fields) = content.as_object_mut() {
+        fields.remove("id");
+        fields.remove("score");
+        fields.remove("result_metadata");
+    }
+    content
+}
+
+pub fn write_json(path: &Path, value: &Value) {
+    let file = File::create(path).expect(&format!(
+        "Failed to create {}",
+        path.display()
+    ));
+    to_writer_pretty(file, value).expect(&format!(
+        "Failed to write {}",
+        path.display()
+    ));
+}
+
+// Count the documents in a backup, making sure each one reads back.
+fn verified_document_count(documents: &Path) -> u64 {
+    let mut count = 0;
+    for entry in read_dir(documents).expect(
+        "Failed to read backup documents directory",
+    )
+    {
+        let path = entry.expect("Failed to read backup entry").path();
+        match File::open(&path).map(from_reader::<_, Value>) {
+            Ok(Ok(_)) => count += 1,
+            _ => println!("Backup file {} is not readable", path.display()),
+        }
+    }
+    count
+}
+
+// Export every document in the collection along with its metadata and
+// configuration. Returns the backup directory when the export is
+// complete and every file reads back, None otherwise.
+pub fn backup_collection(
+    creds: &Credentials,
+    env_id: &str,
+    collection: &Value,
+    dir: &str,
+) -> Option<PathBuf> {
+    let col_id = collection["collection_id"].as_str().expect(
+        "Internal error: missing collection_id",
+    );
+    let target = Path::new(dir).join(col_id);
+    let documents = target.join("documents");
+    create_dir_all(&documents).expect("Failed to create backup directory");
+
+    write_json(&target.join("collection.json"), collection);
+    let configuration_id = collection["configuration_id"].as_str().unwrap_or(
+        "",
+    );
+    match configuration::detail(creds, env_id, configuration_id) {
+        Ok(config) => write_json(&target.join("configuration.json"), &config),
+        Err(e) => {
+            println!("Failed to lookup configuration {}", e);
+            return None;
+        }
+    }
+
+    let mut exported = 0;
+    let matching_results =
+        match each_result(creds, env_id, col_id, all_documents, |result| {
+            let doc_id = result["id"].as_str().expect(
+                "Internal error: missing document id",
+            );
+            write_json(
+                &documents.join(format!("{}.json", doc_id)),
+                &document_content(result),
+            );
+            exported += 1;
+        }) {
+            Ok(matching_results) => matching_results,
+            Err(e) => {
+                println!("Failed to query collection {}", e);
+                return None;
+            }
+        };
+
+    let available =
+        collection["document_counts"]["available"].as_u64().unwrap_or(0);
+    let verified = verified_document_count(&documents);
+    println!(
+        "Exported {} of {} documents from collection {} to {}",
+        verified,
+        matching_results,
+        collection["name"],
+        target.display()
+    );
+    if matching_results > RESULT_WINDOW {
+        println!(
+            "Collection has more than {} documents, the most a \
+             query can page through",
+            RESULT_WINDOW
+        );
+        None
+    } else if verified != exported || verified != matching_results ||
+               verified < available
+    {
+        println!(
+            "Backup is incomplete: {} available, {} matched, {} exported, {} \
+             verified",
+            available,
+            matching_results,
+            exported,
+            verified
+        );
+        None
+    } else {
+        Some(target)
+    }
+}
@@ -0,0 +1,226 @@
+use clap;
+use crossbeam::sync::MsQueue;
+use hyper::status::StatusCode;
+
+use serde_json::{Value, to_string};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use wdsapi::common::{ApiError, Credentials};
+
+// Shared machinery for commands that send one request per document:
+// a pool of worker threads fed from a queue, paced by the main thread,
+// with retries and a final report.
+
+pub struct Context {
+    pub creds: Credentials,
+    pub env_id: String,
+    pub col_id: String,
+    retries: u32,
+    pace: Duration,
+    tick: AtomicUsize,
+    too_many_requests: AtomicUsize,
+    other_errors: AtomicUsize,
+    success: AtomicUsize,
+    failure: AtomicUsize,
+}
+
+impl Context {
+    pub fn new(
+        creds: &Credentials,
+        env_id: &str,
+        col_id: &str,
+        matches: &clap::ArgMatches,
+    ) -> Context {
+        let retries: u32 =
+            matches.value_of("retries").unwrap_or("2").parse().expect(
+                "Retries must be an integer",
+            );
+        let pace: u64 =
+            matches.value_of("pace").unwrap_or("500").parse().expect(
+                "Pace must be an integer",
+            );
+        Context {
+            creds: creds.clone(),
+            env_id: env_id.to_string(),
+            col_id: col_id.to_string(),
+            retries: retries,
+            pace: Duration::from_millis(pace),
+            tick: AtomicUsize::new(0),
+            too_many_requests: AtomicUsize::new(0),
+            other_errors: AtomicUsize::new(0),
+            success: AtomicUsize::new(0),
+            failure: AtomicUsize::new(0),
+        }
+    }
+
+    // How many items have been given up on so far.
+    pub fn failures(&self) -> usize {
+        self.failure.load(Ordering::Relaxed)
+    }
+}
+
+pub fn thread_count(matches: &clap::ArgMatches) -> u32 {
+    matches.value_of("threads").unwrap_or("64").parse().expect(
+        "Threads must be an integer",
+    )
+}
+
+// `verb` is the past tense used in the report, like "Sent" or "Deleted".
+pub fn final_report(duration: Duration, context: &Context, verb: &str) -> () {
+    println!(
+        "\n{} {} documents in {} seconds",
+        verb,
+        context.success.load(Ordering::Relaxed),
+        duration.as_secs()
+    );
+    println!(
+        "Hit {} TooManyRequests (429) responses and {} unexpected errors",
+        context.too_many_requests.load(Ordering::Relaxed),
+        context.other_errors.load(Ordering::Relaxed)
+    );
+    println!(
+        "{} documents failed",
+        context.failure.load(Ordering::Relaxed)
+    );
+}
+
+// Call `request` until it succeeds, backing off when the service reports
+// TooManyRequests and giving up after `retries` other errors. `label`
+// prefixes every line printed and `action` describes the request, like
+// "create document". Returns true when the request succeeded.
+pub fn with_retry<F>(
+    context: &Context,
+    label: &str,
+    action: &str,
+    request: F,
+) -> bool
+where
+    F: Fn() -> Result<Value, ApiError>,
+{
+    let mut unexplained_error_count = 0;
+    loop {
+        match request() {
+            Ok(response) => {
+                context.success.fetch_add(1, Ordering::Relaxed);
+                println!(
+                    "{} {}",
+                    label,
+                    to_string(&response).unwrap_or_default()
+                );
+                return true;
+            }
+            Err(e) => {
+                if let ApiError::Service(ref se) = e {
+                    if se.status_code == StatusCode::TooManyRequests {
+                        context.too_many_requests.fetch_add(
+                            1,
+                            Ordering::Relaxed,
+                        );
+                        // The service says we're going too fast.
+                        // Tell the main pace to wait four ticks,
+                        // also double sleep here and resend.
+                        context.tick.fetch_add(4, Ordering::Relaxed);
+                        println!("{} sleep then retry after {}", label, e);
+                        thread::sleep(
+                            context.pace.checked_mul(2).unwrap_or(context.pace),
+                        );
+                        continue;
+                    }
+                }
+                context.other_errors.fetch_add(1, Ordering::Relaxed);
+                unexplained_error_count += 1;
+                if unexplained_error_count <= context.retries {
+                    // We will retry, so tell the pace to wait another tick.
+                    context.tick.fetch_add(1, Ordering::Relaxed);
+                    println!(
+                        "{} retry after fail to {} {}",
+                        label,
+                        action,
+                        e
+                    );
+                } else {
+                    context.failure.fetch_add(1, Ordering::Relaxed);
+                    println!(
+                        "{} give up after fail to {} {}",
+                        label,
+                        action,
+                        e
+                    );
+                    return false;
+                }
+            }
+        }
+    }
+}
+
+pub struct Pool {
+    context: Arc<Context>,
+    queue: Arc<MsQueue<String>>,
+    threads: Vec<JoinHandle<()>>,
+    base_time: Instant,
+}
+
+impl Pool {
+    // Fire up `thread_count` threads, each calling `work` for every
+    // item pushed into the pool.
+    pub fn start<F>(context: Arc<Context>, thread_count: u32, work: F) -> Pool
+    where
+        F: Fn(&Context, &str) + Send + Sync + 'static,
+    {
+        let queue = Arc::new(MsQueue::new());
+        let work = Arc::new(work);
+        let threads: Vec<JoinHandle<_>> = (0..thread_count)
+            .map(|_| {
+                let worker_context = context.clone();
+                let worker_queue: Arc<MsQueue<String>> = queue.clone();
+                let worker = work.clone();
+                thread::spawn(move || loop {
+                    let item = worker_queue.pop();
+                    if item.is_empty() {
+                        break;
+                    };
+                    worker(&worker_context, &item);
+                })
+            })
+            .collect();
+        Pool {
+            context: context,
+            queue: queue,
+            threads: threads,
+            base_time: Instant::now(),
+        }
+    }
+
+    // Send work into the thread pool, sleeping as needed to keep pace.
+    pub fn push(&self, item: String) {
+        if item.is_empty() {
+            return;
+        }
+        let pace = self.context.pace;
+        self.context.tick.fetch_add(1, Ordering::Relaxed);
+        self.queue.push(item);
+        while let Some(sleep_duration) =
+            pace.checked_mul(self.context.tick.load(Ordering::Relaxed) as u32)
+                .expect("Ran too long?!")
+                .checked_sub(self.base_time.elapsed())
+        {
+            thread::sleep(sleep_duration);
+        }
+    }
+
+    // Tell the threads to shutdown, wait for them and return the elapsed
+    // time.
+    pub fn finish(self) -> Duration {
+        for _ in 0..self.threads.len() {
+            self.queue.push(String::new());
+        }
+        for thread in self.threads {
+            thread.join().expect("Failed to join thread?!");
+        }
+        self.base_time.elapsed()
+    }
+}
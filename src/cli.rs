@@ -54,10 +54,80 @@ pub fn build_cli() -> App<'static, 'static> {
                 .help("Use the collection with this id"))
             .group(ArgGroup::with_name("selector")
                 .args(&["newest", "oldest", "named", "id"])))
+        .subcommand(SubCommand::with_name("apply")
+            .about("Make the writable environment match a manifest, after \
+                    showing the plan.")
+            .arg(Arg::with_name("manifest")
+                .required(true)
+                .help("Manifest naming the environment, its configurations \
+                       and its collections, as JSON, or as YAML or TOML \
+                       when named .yaml, .yml or .toml"))
+            .arg(Arg::with_name("no-validate")
+                .long("no-validate")
+                .help("Send configurations without checking them against \
+                       the built in schema first"))
+            .arg(Arg::with_name("timeout")
+                .long("timeout")
+                .takes_value(true)
+                .help("How many seconds to wait for a new environment \
+                       before giving up with a non-zero exit; default is to \
+                       wait as long as it takes"))
+            .arg(Arg::with_name("yes")
+                .short("y")
+                .long("yes")
+                .help("Skip the confirmation prompt")))
+        .subcommand(SubCommand::with_name("clone-collection")
+            .about("Create a new collection holding a copy of every document \
+                    in an existing collection.")
+            .arg(Arg::with_name("from")
+                .long("from")
+                .takes_value(true)
+                .required(true)
+                .help("The collection to copy: newest, oldest, name:<name> \
+                       or id:<id>"))
+            .arg(Arg::with_name("to")
+                .long("to")
+                .takes_value(true)
+                .required(true)
+                .help("The name of the new collection"))
+            .arg(Arg::with_name("configuration")
+                .long("configuration")
+                .takes_value(true)
+                .help("The configuration for the new collection: newest, \
+                       oldest, name:<name> or id:<id>; default is the \
+                       configuration of the collection being copied"))
+            .arg(Arg::with_name("timeout")
+                .long("timeout")
+                .takes_value(true)
+                .help("How many seconds to wait for the new collection to \
+                       become active, and again for its documents to finish \
+                       processing; default is 300"))
+            .arg(Arg::with_name("pace")
+                .short("p")
+                .long("pace")
+                .takes_value(true)
+                .help("How many milliseconds to pause between sending \
+                       documents; default is 500"))
+            .arg(Arg::with_name("threads")
+                .short("t")
+                .long("threads")
+                .takes_value(true)
+                .help("The number of add-document threads to start; default \
+                       is 64"))
+            .arg(Arg::with_name("retries")
+                .short("r")
+                .long("retries")
+                .takes_value(true)
+                .help("The number of retries after an unexected error from \
+                       the service; default is 2")))
         .subcommand(SubCommand::with_name("create-collection")
             .visible_alias("cl")
             .about("Create a new collection using the most recently created \
                     configuration")
+            .arg(Arg::with_name("if-not-exists")
+                .long("if-not-exists")
+                .help("When a collection with this name already exists, \
+                       print it instead of creating another"))
             .arg(Arg::with_name("name")
                 .required(true)
                 .help("The name of the collection."))
@@ -67,6 +137,15 @@ pub fn build_cli() -> App<'static, 'static> {
                 .long("description")
                 .takes_value(true)
                 .help("Description text for the collection."))
+            .arg(Arg::with_name("wait")
+                .long("wait")
+                .help("Wait for the new collection to become active."))
+            .arg(Arg::with_name("timeout")
+                .long("timeout")
+                .takes_value(true)
+                .requires("wait")
+                .help("How many seconds to wait before giving up with a \
+                       non-zero exit; default is 300"))
             .arg(Arg::with_name("newest")
                 .short("n")
                 .long("newest")
@@ -91,12 +170,40 @@ pub fn build_cli() -> App<'static, 'static> {
         .subcommand(SubCommand::with_name("create-configuration")
             .visible_alias("cn")
             .about("Create a new configuration.")
+            .arg(Arg::with_name("if-not-exists")
+                .long("if-not-exists")
+                .help("When a configuration with this name already exists, \
+                       print it instead of creating another"))
             .arg(Arg::with_name("configuration")
                 .required(true)
-                .help("File containing the configuration JSON.")))
+                .help("File containing the configuration as JSON, or as \
+                       YAML or TOML when named .yaml, .yml or .toml. \
+                       ${VAR} in any string is replaced by the environment \
+                       variable VAR; use $$ for a literal $."))
+            .arg(Arg::with_name("set")
+                .long("set")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Set a value in the configuration, like \
+                       --set enrichments.0.options.language=fr; the value \
+                       is read as JSON when possible, otherwise as a \
+                       string"))
+            .arg(Arg::with_name("no-validate")
+                .long("no-validate")
+                .help("Send the configuration without checking it against \
+                       the built in schema first"))
+            .arg(Arg::with_name("render-only")
+                .long("render-only")
+                .help("Print the configuration JSON that would be sent, \
+                       without creating it")))
         .subcommand(SubCommand::with_name("create-environment")
             .visible_alias("ce")
             .about("Create a writable environment")
+            .arg(Arg::with_name("if-not-exists")
+                .long("if-not-exists")
+                .help("When an environment with this name already exists, \
+                       print it instead of creating another"))
             .arg(Arg::with_name("name")
                 .required(true)
                 .help("The name of the environment."))
@@ -104,7 +211,9 @@ pub fn build_cli() -> App<'static, 'static> {
                 .short("s")
                 .long("size")
                 .takes_value(true)
-                .help("The size environment to create."))
+                .help("The size environment to create: a name like XS, \
+                       see list-environment-sizes, or the number older API \
+                       versions took."))
             .arg(Arg::with_name("description")
                 .short("d")
                 .long("desc")
@@ -113,13 +222,69 @@ pub fn build_cli() -> App<'static, 'static> {
                 .help("Description text for the environment."))
             .arg(Arg::with_name("wait")
                 .long("wait")
-                .help("Wait for the new environment to become active.")))
+                .help("Wait for the new environment to become active."))
+            .arg(Arg::with_name("timeout")
+                .long("timeout")
+                .takes_value(true)
+                .requires("wait")
+                .help("How many seconds to wait before giving up with a \
+                       non-zero exit; default is to wait as long as it \
+                       takes")))
         .subcommand(SubCommand::with_name("delete-collection")
             .visible_alias("dl")
             .about("Delete a collection.")
+            .arg(Arg::with_name("wait")
+                .long("wait")
+                .help("Wait until the service reports the deletion is \
+                       complete"))
+            .arg(Arg::with_name("timeout")
+                .long("timeout")
+                .takes_value(true)
+                .requires("wait")
+                .help("How many seconds to wait before giving up with a \
+                       non-zero exit; default is 300"))
+            .arg(Arg::with_name("backup")
+                .long("backup")
+                .takes_value(true)
+                .help("Export the collection's documents, configuration and \
+                       details into this directory, and only delete the \
+                       collection once the export is verified"))
+            .arg(Arg::with_name("yes")
+                .short("y")
+                .long("yes")
+                .help("Skip the confirmation prompt"))
             .arg(Arg::with_name("all")
                 .long("all")
-                .help("Delete all existing collections"))
+                .help("Delete all existing collections, or those matching \
+                       every filter given"))
+            .arg(Arg::with_name("name-glob")
+                .long("name-glob")
+                .takes_value(true)
+                .requires("all")
+                .help("With --all, only collections with a name matching \
+                       this glob pattern"))
+            .arg(Arg::with_name("name-regex")
+                .long("name-regex")
+                .takes_value(true)
+                .requires("all")
+                .help("With --all, only collections with a name matching \
+                       this regular expression"))
+            .arg(Arg::with_name("created-before")
+                .long("created-before")
+                .takes_value(true)
+                .requires("all")
+                .help("With --all, only collections created before this \
+                       date, like 2017-06-30 or 2017-06-30T12:00:00Z (UTC)"))
+            .arg(Arg::with_name("empty-only")
+                .long("empty-only")
+                .requires("all")
+                .help("With --all, only collections without documents"))
+            .arg(Arg::with_name("failed-over")
+                .long("failed-over")
+                .takes_value(true)
+                .requires("all")
+                .help("With --all, only collections with more than this \
+                       many failed documents"))
             .arg(Arg::with_name("newest")
                 .short("n")
                 .long("newest")
@@ -144,6 +309,20 @@ pub fn build_cli() -> App<'static, 'static> {
         .subcommand(SubCommand::with_name("delete-configuration")
             .visible_alias("dn")
             .about("Delete a configuration.")
+            .arg(Arg::with_name("wait")
+                .long("wait")
+                .help("Wait until the service reports the deletion is \
+                       complete"))
+            .arg(Arg::with_name("timeout")
+                .long("timeout")
+                .takes_value(true)
+                .requires("wait")
+                .help("How many seconds to wait before giving up with a \
+                       non-zero exit; default is 300"))
+            .arg(Arg::with_name("yes")
+                .short("y")
+                .long("yes")
+                .help("Skip the confirmation prompt"))
             .arg(Arg::with_name("all")
                 .long("all")
                 .help("Delete all existing configurations, except for the \
@@ -167,7 +346,201 @@ pub fn build_cli() -> App<'static, 'static> {
                 .args(&["all", "newest", "named", "id"])))
         .subcommand(SubCommand::with_name("delete-environment")
             .visible_alias("de")
-            .about("Delete the writable environment"))
+            .about("Delete the writable environment")
+            .arg(Arg::with_name("wait")
+                .long("wait")
+                .help("Wait until the service reports the deletion is \
+                       complete"))
+            .arg(Arg::with_name("timeout")
+                .long("timeout")
+                .takes_value(true)
+                .requires("wait")
+                .help("How many seconds to wait before giving up with a \
+                       non-zero exit; default is 300"))
+            .arg(Arg::with_name("yes")
+                .short("y")
+                .long("yes")
+                .help("Skip the confirmation prompt")))
+        .subcommand(SubCommand::with_name("diff-configuration")
+            .about("Show the differences between two configurations.")
+            .arg(Arg::with_name("left")
+                .required(true)
+                .help("The first configuration: newest, oldest, \
+                       name:<name>, id:<id>, or a local file (optionally \
+                       prefixed with file:)"))
+            .arg(Arg::with_name("right")
+                .required(true)
+                .help("The second configuration: newest, oldest, \
+                       name:<name>, id:<id>, or a local file (optionally \
+                       prefixed with file:)")))
+        .subcommand(SubCommand::with_name("evaluate")
+            .about("Measure how well a collection answers a set of judged \
+                    queries.")
+            .arg(Arg::with_name("judgments")
+                .long("judgments")
+                .takes_value(true)
+                .required(true)
+                .help("File listing queries, each with a query or \
+                       natural_language_query, an optional filter, and \
+                       relevant: a list of document ids or a map of \
+                       document id to grade; JSON, or YAML or TOML when \
+                       named .yaml, .yml or .toml"))
+            .arg(Arg::with_name("k")
+                .short("k")
+                .takes_value(true)
+                .help("How many results of each query to score; default \
+                       is 10"))
+            .arg(Arg::with_name("compare")
+                .long("compare")
+                .takes_value(true)
+                .help("A second collection to score side by side: newest, \
+                       oldest, name:<name> or id:<id>"))
+            .arg(Arg::with_name("threads")
+                .short("t")
+                .long("threads")
+                .takes_value(true)
+                .help("How many queries to run at once; default is 4"))
+            .arg(Arg::with_name("retries")
+                .short("r")
+                .long("retries")
+                .takes_value(true)
+                .help("The number of retries of a failed query, backing off \
+                       from one second; default is 2"))
+            .arg(Arg::with_name("newest")
+                .short("n")
+                .long("newest")
+                .help("Use most recently created collection, default if no \
+                       other selection is made"))
+            .arg(Arg::with_name("oldest")
+                .short("o")
+                .long("oldest")
+                .help("Use the collection created the longest time ago"))
+            .arg(Arg::with_name("named")
+                .short("m")
+                .long("named")
+                .takes_value(true)
+                .help("Use the collection with a name matching this"))
+            .arg(Arg::with_name("id")
+                .short("i")
+                .long("with-id")
+                .takes_value(true)
+                .help("Use the collection with this id"))
+            .group(ArgGroup::with_name("selector")
+                .args(&["newest", "oldest", "named", "id"])))
+        .subcommand(SubCommand::with_name("export-collection")
+            .about("Write every document in a collection, or those \
+                    matching a filter, as JSON lines or CSV.")
+            .arg(Arg::with_name("filter")
+                .short("f")
+                .long("filter")
+                .takes_value(true)
+                .help("Only export documents matching this filter"))
+            .arg(Arg::with_name("where")
+                .long("where")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Only documents where field=value exactly, like \
+                       --where extracted_metadata.title=Summary"))
+            .arg(Arg::with_name("where-contains")
+                .long("where-contains")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Only documents where field=value, with the field \
+                       containing the value"))
+            .arg(Arg::with_name("where-gt")
+                .long("where-gt")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Only documents where field=value, with the field \
+                       greater than the value"))
+            .arg(Arg::with_name("where-lt")
+                .long("where-lt")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Only documents where field=value, with the field \
+                       less than the value"))
+            .arg(Arg::with_name("where-exists")
+                .long("where-exists")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Only documents that have this field"))
+            .arg(Arg::with_name("show-filter")
+                .long("show-filter")
+                .help("Print the filter built from --filter and the \
+                       --where flags to standard error"))
+            .arg(Arg::with_name("format")
+                .long("format")
+                .takes_value(true)
+                .possible_values(&["jsonl", "csv"])
+                .help("jsonl for one JSON document per line, the default, \
+                       or csv with one column for each of --fields"))
+            .arg(Arg::with_name("fields")
+                .long("fields")
+                .takes_value(true)
+                .use_delimiter(true)
+                .help("Comma separated fields to export, like \
+                       id,extracted_metadata.title; nested values, and \
+                       the values a path finds across a list, like \
+                       enriched_text.entities.text, are written as JSON \
+                       in CSV"))
+            .arg(Arg::with_name("partition-field")
+                .long("partition-field")
+                .takes_value(true)
+                .help("A sortable field every document has, used to export \
+                       collections with more than 10000 matching documents \
+                       in several queries"))
+            .arg(Arg::with_name("out")
+                .long("out")
+                .takes_value(true)
+                .help("File to write to, instead of standard output"))
+            .arg(Arg::with_name("read-only")
+                .long("read-only")
+                .short("r")
+                .help("Use the read only environment"))
+            .arg(Arg::with_name("writable")
+                .long("writable")
+                .short("w")
+                .help("Use the writable environment, default if no other \
+                       environment selection is made"))
+            .arg(Arg::with_name("newest")
+                .short("n")
+                .long("newest")
+                .help("Use most recently created collection, default if no \
+                       other collection selection is made"))
+            .arg(Arg::with_name("oldest")
+                .short("o")
+                .long("oldest")
+                .help("Use the collection created the longest time ago"))
+            .arg(Arg::with_name("named")
+                .short("m")
+                .long("named")
+                .takes_value(true)
+                .help("Use the collection with a name matching this"))
+            .arg(Arg::with_name("id")
+                .short("i")
+                .long("with-id")
+                .takes_value(true)
+                .help("Use the collection with this id"))
+            .group(ArgGroup::with_name("environment")
+                .args(&["read-only", "writable"]))
+            .group(ArgGroup::with_name("collection")
+                .args(&["newest", "oldest", "named", "id"])))
+        .subcommand(SubCommand::with_name("export-environment")
+            .about("Write the writable environment's layout as a manifest \
+                    for apply, with each configuration in its own file.")
+            .arg(Arg::with_name("directory")
+                .required(true)
+                .help("Directory to write manifest.json and \
+                       configurations/ into")))
+        .subcommand(SubCommand::with_name("list-environment-sizes")
+            .about("List the environment sizes, with the disk, memory, \
+                    document and collection limits the service reports for \
+                    each existing environment."))
         .subcommand(SubCommand::with_name("overview")
             .visible_alias("o")
             .about("Displays information about existing resources.")
@@ -175,6 +548,74 @@ pub fn build_cli() -> App<'static, 'static> {
                 .short("g")
                 .long("guid")
                 .help("Display the GUID for each item")))
+        .subcommand(SubCommand::with_name("plan")
+            .about("Show what apply would create, update and delete to \
+                    make the writable environment match a manifest.")
+            .arg(Arg::with_name("manifest")
+                .required(true)
+                .help("Manifest naming the environment, its configurations \
+                       and its collections, as JSON, or as YAML or TOML \
+                       when named .yaml, .yml or .toml"))
+            .arg(Arg::with_name("no-validate")
+                .long("no-validate")
+                .help("Skip checking configurations against the built in \
+                       schema")))
+        .subcommand(SubCommand::with_name("purge-collection")
+            .about("Delete every document in a collection, keeping the \
+                    collection itself.")
+            .arg(Arg::with_name("yes")
+                .short("y")
+                .long("yes")
+                .help("Skip the confirmation prompt"))
+            .arg(Arg::with_name("no-wait")
+                .long("no-wait")
+                .help("Return once every delete is sent, without waiting \
+                       for the document counts to reach zero"))
+            .arg(Arg::with_name("timeout")
+                .long("timeout")
+                .takes_value(true)
+                .help("How many seconds to wait, between rounds of deletes \
+                       and for the counts to reach zero, before giving up \
+                       with a non-zero exit; default is 300"))
+            .arg(Arg::with_name("pace")
+                .short("p")
+                .long("pace")
+                .takes_value(true)
+                .help("How many milliseconds to pause between deleting \
+                       documents; default is 500"))
+            .arg(Arg::with_name("threads")
+                .short("t")
+                .long("threads")
+                .takes_value(true)
+                .help("The number of delete-document threads to start; \
+                       default is 64"))
+            .arg(Arg::with_name("retries")
+                .short("r")
+                .long("retries")
+                .takes_value(true)
+                .help("The number of retries after an unexected error from \
+                       the service; default is 2"))
+            .arg(Arg::with_name("newest")
+                .short("n")
+                .long("newest")
+                .help("Purge the most recently created collection, default \
+                       if no other selection is made"))
+            .arg(Arg::with_name("oldest")
+                .short("o")
+                .long("oldest")
+                .help("Purge the collection created the longest time ago"))
+            .arg(Arg::with_name("named")
+                .short("m")
+                .long("named")
+                .takes_value(true)
+                .help("Purge the collection with a name matching this"))
+            .arg(Arg::with_name("id")
+                .short("i")
+                .long("with-id")
+                .takes_value(true)
+                .help("Purge the collection with this id"))
+            .group(ArgGroup::with_name("selector")
+                .args(&["newest", "oldest", "named", "id"])))
         .subcommand(SubCommand::with_name("query")
             .visible_alias("q")
             .about("Query a collection.")
@@ -183,6 +624,15 @@ pub fn build_cli() -> App<'static, 'static> {
                 .long("aggregation")
                 .takes_value(true)
                 .help("The aggregation string for the query"))
+            .arg(Arg::with_name("aggregation-output")
+                .long("aggregation-output")
+                .takes_value(true)
+                .possible_values(&["text", "csv", "json"])
+                .conflicts_with("all")
+                .help("How to show aggregation results: text for tables, \
+                       sparklines and trees, the default on a terminal; csv \
+                       for spreadsheets; json for the full response, the \
+                       default otherwise"))
             .arg(Arg::with_name("count")
                 .short("C")
                 .long("count")
@@ -192,11 +642,61 @@ pub fn build_cli() -> App<'static, 'static> {
                 .long("offset")
                 .takes_value(true)
                 .help("The offset of the first result returned by the query"))
+            .arg(Arg::with_name("all")
+                .long("all")
+                .conflicts_with_all(&["count", "offset"])
+                .help("Page through every matching result, up to the \
+                       service's limit of 10000, writing one JSON object \
+                       per line"))
+            .arg(Arg::with_name("out")
+                .long("out")
+                .takes_value(true)
+                .requires("all")
+                .help("File to write the --all results to, instead of \
+                       standard output"))
             .arg(Arg::with_name("filter")
                 .short("f")
                 .long("filter")
                 .takes_value(true)
                 .help("The filter string for the query"))
+            .arg(Arg::with_name("where")
+                .long("where")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Only documents where field=value exactly, like \
+                       --where extracted_metadata.title=Summary"))
+            .arg(Arg::with_name("where-contains")
+                .long("where-contains")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Only documents where field=value, with the field \
+                       containing the value"))
+            .arg(Arg::with_name("where-gt")
+                .long("where-gt")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Only documents where field=value, with the field \
+                       greater than the value"))
+            .arg(Arg::with_name("where-lt")
+                .long("where-lt")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Only documents where field=value, with the field \
+                       less than the value"))
+            .arg(Arg::with_name("where-exists")
+                .long("where-exists")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Only documents that have this field"))
+            .arg(Arg::with_name("show-filter")
+                .long("show-filter")
+                .help("Print the filter built from --filter and the \
+                       --where flags to standard error"))
             .arg(Arg::with_name("query")
                 .short("q")
                 .long("query")
@@ -221,6 +721,21 @@ pub fn build_cli() -> App<'static, 'static> {
                 .long("return_hierarchy")
                 .takes_value(true)
                 .help("The return hierarchy string for the query"))
+            .arg(Arg::with_name("output")
+                .long("output")
+                .takes_value(true)
+                .possible_values(&["json", "table"])
+                .conflicts_with("all")
+                .help("json for the full response, the default, or table \
+                       for one line per result"))
+            .arg(Arg::with_name("fields")
+                .long("fields")
+                .takes_value(true)
+                .use_delimiter(true)
+                .conflicts_with("return_hierarchy")
+                .help("Comma separated fields to return, like \
+                       id,score,extracted_metadata.title; these are the \
+                       table columns, id and score by default"))
             .arg(Arg::with_name("read-only")
                 .long("read-only")
                 .short("r")
@@ -243,16 +758,26 @@ pub fn build_cli() -> App<'static, 'static> {
                 .short("m")
                 .long("named")
                 .takes_value(true)
-                .help("Use the collection with a name matching this"))
+                .multiple(true)
+                .number_of_values(1)
+                .help("Use the collection with a name matching this; \
+                       repeat to query several collections"))
             .arg(Arg::with_name("id")
                 .short("i")
                 .long("with-id")
                 .takes_value(true)
-                .help("Use the collection with this id"))
+                .multiple(true)
+                .number_of_values(1)
+                .help("Use the collection with this id; repeat to query \
+                       several collections"))
+            .arg(Arg::with_name("all-collections")
+                .long("all-collections")
+                .help("Query every collection in the environment"))
             .group(ArgGroup::with_name("environment")
                 .args(&["read-only", "writable"]))
             .group(ArgGroup::with_name("collection")
-                .args(&["newest", "oldest", "named", "id"])))
+                .args(&["newest", "oldest", "named", "id", "all-collections"])
+                .multiple(true)))
         .subcommand(SubCommand::with_name("notices")
             .visible_alias("n")
             .about("Query ingestion notices for a collection.")
@@ -347,6 +872,58 @@ pub fn build_cli() -> App<'static, 'static> {
                 .help("Use the configuration with this id"))
             .group(ArgGroup::with_name("selector")
                 .args(&["newest", "oldest", "named", "id"])))
+        .subcommand(SubCommand::with_name("resize-environment")
+            .about("Make the writable environment larger and wait for it \
+                    to become active again.")
+            .arg(Arg::with_name("size")
+                .required(true)
+                .help("The new size, a name like S; see \
+                       list-environment-sizes"))
+            .arg(Arg::with_name("timeout")
+                .long("timeout")
+                .takes_value(true)
+                .help("How many seconds to wait before giving up with a \
+                       non-zero exit; default is 300"))
+            .arg(Arg::with_name("yes")
+                .short("y")
+                .long("yes")
+                .help("Skip the confirmation prompt")))
+        .subcommand(SubCommand::with_name("shell")
+            .about("Query a collection interactively, loading service \
+                    information only once.")
+            .arg(Arg::with_name("read-only")
+                .long("read-only")
+                .short("r")
+                .help("Use the read only environment"))
+            .arg(Arg::with_name("writable")
+                .long("writable")
+                .short("w")
+                .help("Use the writable environment, default if no other \
+                       environment selection is made"))
+            .arg(Arg::with_name("newest")
+                .short("n")
+                .long("newest")
+                .help("Start with the most recently created collection, \
+                       default if no other collection selection is made"))
+            .arg(Arg::with_name("oldest")
+                .short("o")
+                .long("oldest")
+                .help("Start with the collection created the longest time \
+                       ago"))
+            .arg(Arg::with_name("named")
+                .short("m")
+                .long("named")
+                .takes_value(true)
+                .help("Start with the collection with a name matching this"))
+            .arg(Arg::with_name("id")
+                .short("i")
+                .long("with-id")
+                .takes_value(true)
+                .help("Start with the collection with this id"))
+            .group(ArgGroup::with_name("environment")
+                .args(&["read-only", "writable"]))
+            .group(ArgGroup::with_name("collection")
+                .args(&["newest", "oldest", "named", "id"])))
         .subcommand(SubCommand::with_name("show-environment")
             .visible_alias("se")
             .about("Displays detailed information about an environment.")
@@ -421,10 +998,46 @@ pub fn build_cli() -> App<'static, 'static> {
         .subcommand(SubCommand::with_name("delete-document")
             .visible_alias("dd")
             .about("Delete a document from a collection.")
+            .arg(Arg::with_name("wait")
+                .long("wait")
+                .help("Wait until the service reports the deletion is \
+                       complete"))
+            .arg(Arg::with_name("timeout")
+                .long("timeout")
+                .takes_value(true)
+                .requires("wait")
+                .help("How many seconds to wait before giving up with a \
+                       non-zero exit; default is 300"))
+            .arg(Arg::with_name("yes")
+                .short("y")
+                .long("yes")
+                .help("Skip the confirmation prompt"))
             .arg(Arg::with_name("document_id")
-                .required(true)
                 .multiple(true)
                 .help("The document_id(s) to delete."))
+            .arg(Arg::with_name("ids-from")
+                .long("ids-from")
+                .takes_value(true)
+                .help("A file containing document_ids to delete, one per \
+                       line; use '-' to read from stdin, which needs --yes"))
+            .arg(Arg::with_name("pace")
+                .short("p")
+                .long("pace")
+                .takes_value(true)
+                .help("How many milliseconds to pause between deleting \
+                       documents; default is 500"))
+            .arg(Arg::with_name("threads")
+                .short("t")
+                .long("threads")
+                .takes_value(true)
+                .help("The number of delete-document threads to start; \
+                       default is 64"))
+            .arg(Arg::with_name("retries")
+                .short("r")
+                .long("retries")
+                .takes_value(true)
+                .help("The number of retries after an unexected error from \
+                       the service; default is 2"))
             .arg(Arg::with_name("newest")
                 .short("n")
                 .long("newest")
@@ -444,6 +1057,10 @@ pub fn build_cli() -> App<'static, 'static> {
                 .long("with-id")
                 .takes_value(true)
                 .help("Use the collection with this id"))
+            .group(ArgGroup::with_name("documents")
+                .required(true)
+                .multiple(true)
+                .args(&["document_id", "ids-from"]))
             .group(ArgGroup::with_name("selector")
                 .args(&["newest", "oldest", "named", "id"])))
         .subcommand(SubCommand::with_name("add-document")
@@ -476,6 +1093,12 @@ pub fn build_cli() -> App<'static, 'static> {
                 .long("document-id")
                 .takes_value(true)
                 .help("A hexadecimal integer for the first document-id"))
+            .arg(Arg::with_name("filename-ids")
+                .long("filename-ids")
+                .conflicts_with("document-id")
+                .help("Use each file's name, without its extension, as its \
+                       document-id; this restores a delete-collection \
+                       --backup"))
             .arg(Arg::with_name("newest")
                 .short("n")
                 .long("newest")
@@ -497,6 +1120,117 @@ pub fn build_cli() -> App<'static, 'static> {
                 .help("Target the collection with this id"))
             .group(ArgGroup::with_name("selector")
                 .args(&["newest", "oldest", "named", "id"])))
+        .subcommand(SubCommand::with_name("validate-configuration")
+            .visible_alias("vn")
+            .about("Check a configuration file against the configuration \
+                    format, without contacting the service.")
+            .arg(Arg::with_name("configuration")
+                .required(true)
+                .help("File containing the configuration as JSON, or as \
+                       YAML or TOML when named .yaml, .yml or .toml. \
+                       ${VAR} in any string is replaced by the environment \
+                       variable VAR; use $$ for a literal $."))
+            .arg(Arg::with_name("set")
+                .long("set")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Set a value in the configuration, like \
+                       --set enrichments.0.options.language=fr; the value \
+                       is read as JSON when possible, otherwise as a \
+                       string")))
+        .subcommand(SubCommand::with_name("update-collection")
+            .visible_alias("uc")
+            .about("Change a collection's name, description or \
+                    configuration, keeping its documents.")
+            .arg(Arg::with_name("name")
+                .long("name")
+                .takes_value(true)
+                .help("The new name for the collection"))
+            .arg(Arg::with_name("description")
+                .long("description")
+                .takes_value(true)
+                .help("The new description for the collection"))
+            .arg(Arg::with_name("configuration")
+                .long("configuration")
+                .takes_value(true)
+                .help("The configuration the collection should use: newest, \
+                       oldest, name:<name> or id:<id>; documents already in \
+                       the collection are not reprocessed"))
+            .group(ArgGroup::with_name("changes")
+                .args(&["name", "description", "configuration"])
+                .multiple(true)
+                .required(true))
+            .arg(Arg::with_name("newest")
+                .short("n")
+                .long("newest")
+                .help("Update the most recently created collection, default \
+                       if no other selection is made"))
+            .arg(Arg::with_name("oldest")
+                .short("o")
+                .long("oldest")
+                .help("Update the collection created the longest time ago"))
+            .arg(Arg::with_name("named")
+                .short("m")
+                .long("named")
+                .takes_value(true)
+                .help("Update the collection with a name matching this"))
+            .arg(Arg::with_name("id")
+                .short("i")
+                .long("with-id")
+                .takes_value(true)
+                .help("Update the collection with this id"))
+            .group(ArgGroup::with_name("selector")
+                .args(&["newest", "oldest", "named", "id"])))
+        .subcommand(SubCommand::with_name("update-configuration")
+            .visible_alias("un")
+            .about("Update a configuration from a file, after showing the \
+                    differences.")
+            .arg(Arg::with_name("configuration")
+                .required(true)
+                .help("File containing the configuration as JSON, or as \
+                       YAML or TOML when named .yaml, .yml or .toml. \
+                       ${VAR} in any string is replaced by the environment \
+                       variable VAR; use $$ for a literal $."))
+            .arg(Arg::with_name("set")
+                .long("set")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Set a value in the configuration, like \
+                       --set enrichments.0.options.language=fr; the value \
+                       is read as JSON when possible, otherwise as a \
+                       string"))
+            .arg(Arg::with_name("no-validate")
+                .long("no-validate")
+                .help("Send the configuration without checking it against \
+                       the built in schema first"))
+            .arg(Arg::with_name("yes")
+                .short("y")
+                .long("yes")
+                .help("Skip the confirmation prompt"))
+            .arg(Arg::with_name("newest")
+                .short("n")
+                .long("newest")
+                .help("Update the most recently created configuration, \
+                       default if no other selection is made"))
+            .arg(Arg::with_name("oldest")
+                .short("o")
+                .long("oldest")
+                .help("Update the configuration created the longest time \
+                       ago"))
+            .arg(Arg::with_name("named")
+                .short("m")
+                .long("named")
+                .takes_value(true)
+                .help("Update the configuration with a name matching this"))
+            .arg(Arg::with_name("id")
+                .short("i")
+                .long("with-id")
+                .takes_value(true)
+                .help("Update the configuration with this id"))
+            .group(ArgGroup::with_name("selector")
+                .args(&["newest", "oldest", "named", "id"])))
         .subcommand(SubCommand::with_name("show-configuration")
             .visible_alias("sn")
             .about("Displays detailed information about a configuration.")
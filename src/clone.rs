@@ -0,0 +1,208 @@
+use add::{filename_id, send_file_with_retry};
+use backup::backup_collection;
+use bulk::{Context, Pool, final_report, thread_count};
+use clap;
+use confirm::document_counts;
+use create::{already_exists, with_name};
+use info::discovery_service_info;
+use select::{collection_with_selector, configuration_with_selector,
+             writable_environment};
+use serde_json::{Value, to_string_pretty};
+use std;
+use std::env::temp_dir;
+use std::fs::{read_dir, remove_dir_all};
+use std::path::PathBuf;
+use std::process;
+use std::sync::Arc;
+use wait::{Poll, wait_for_status};
+use wdsapi::collection;
+use wdsapi::collection::NewCollection;
+use wdsapi::common::Credentials;
+
+fn processing(collection: &Value) -> u64 {
+    collection["document_counts"]["processing"].as_u64().unwrap_or(0)
+}
+
+// The temporary export, removed when dropped.
+struct ExportDir(PathBuf);
+
+impl Drop for ExportDir {
+    fn drop(&mut self) {
+        let _ = remove_dir_all(&self.0);
+    }
+}
+
+// Export `source` into `export_dir`, create the new collection and send it
+// the exported documents. Returns the new collection_id, or None after
+// reporting a failure.
+fn copy_collection(
+    creds: &Credentials,
+    env_id: &str,
+    source: &Value,
+    col_options: NewCollection,
+    export_dir: &ExportDir,
+    matches: &clap::ArgMatches,
+) -> Option<String> {
+    let export_dir = export_dir.0.to_str().expect(
+        "Temporary directory name is not valid UTF-8",
+    );
+    let documents = match backup_collection(creds, env_id, source, export_dir)
+    {
+        Some(target) => target.join("documents"),
+        None => {
+            println!("Not cloning collection {}", source["name"]);
+            return None;
+        }
+    };
+
+    let target = match collection::create(creds, env_id, &col_options) {
+        Ok(response) => {
+            println!(
+                "{}",
+                to_string_pretty(&response).expect(
+                    "Internal error: failed to format \
+                                  create_collection response",
+                )
+            );
+            response
+        }
+        Err(e) => {
+            println!("Failed to create collection {}", e);
+            return None;
+        }
+    };
+    let col_id = target["collection_id"].as_str().expect(
+        "Internal error: missing collection_id",
+    );
+    if !wait_for_status(
+        &mut Poll::new(matches),
+        "Collection",
+        &["active"],
+        || collection::detail(creds, env_id, col_id),
+    )
+    {
+        return None;
+    }
+
+    let context = Arc::new(Context::new(creds, env_id, col_id, matches));
+    let pool = Pool::start(
+        context.clone(),
+        thread_count(matches),
+        |context, filename| {
+            send_file_with_retry(context, &filename_id(filename), filename)
+        },
+    );
+    for entry in read_dir(&documents).expect(
+        "Failed to read exported documents",
+    )
+    {
+        let path = entry.expect("Failed to read exported document").path();
+        if let Some(filename) = path.to_str() {
+            pool.push(filename.to_string());
+        }
+    }
+    let duration = pool.finish();
+    final_report(duration, &context, "Sent");
+    Some(col_id.to_string())
+}
+
+pub fn clone_collection(creds: Credentials, matches: &clap::ArgMatches) {
+    let info = discovery_service_info(creds);
+    let env_info = writable_environment(&info);
+    let env_id = env_info.environment_id.clone();
+    let from = matches.value_of("from").unwrap();
+    let source = match collection_with_selector(&env_info, from) {
+        Some(source) => source,
+        None => {
+            println!(
+                "Expected newest, oldest, name:<name> or id:<id> for --from, \
+                 not {}",
+                from
+            );
+            std::process::exit(1)
+        }
+    };
+    let configuration_id = match matches.value_of("configuration") {
+        Some(selector) => {
+            match configuration_with_selector(&env_info, selector) {
+                Some(ref selected) if selected.is_object() => {
+                    selected["configuration_id"].clone()
+                }
+                _ => {
+                    println!(
+                        "Expected newest, oldest, name:<name> or id:<id> \
+                         naming an existing configuration for \
+                         --configuration, not {}",
+                        selector
+                    );
+                    std::process::exit(1)
+                }
+            }
+        }
+        None => source["configuration_id"].clone(),
+    };
+
+    let to = matches.value_of("to").unwrap();
+    already_exists(
+        matches,
+        "collection",
+        to,
+        with_name(&env_info.collections, to),
+    );
+
+    // Export the documents first, so a failure leaves nothing behind. The
+    // export is removed however the copy ends, so every failure returns
+    // here before exiting.
+    let export_dir = ExportDir(
+        temp_dir().join(format!("wdscli-clone-{}", process::id())),
+    );
+    let copied = copy_collection(
+        &info.creds,
+        &env_id,
+        &source,
+        NewCollection {
+            name: to.to_string(),
+            description: source["description"]
+                .as_str()
+                .map(|d| d.to_string()),
+            configuration_id: configuration_id.as_str().map(|c| {
+                c.to_string()
+            }),
+        },
+        &export_dir,
+        matches,
+    );
+    drop(export_dir);
+    let col_id = match copied {
+        Some(col_id) => col_id,
+        None => std::process::exit(1),
+    };
+    let col_id = col_id.as_str();
+
+    // Let ingestion finish before comparing the two collections.
+    println!("Waiting for documents to finish processing");
+    let mut poll = Poll::new(matches);
+    let mut cloned = Value::Null;
+    loop {
+        match collection::detail(&info.creds, &env_id, col_id) {
+            Ok(detail) => {
+                cloned = detail;
+                if processing(&cloned) == 0 {
+                    break;
+                }
+            }
+            Err(e) => {
+                println!(
+                    "Continuing after collection status check failure {}",
+                    e
+                )
+            }
+        }
+        if !poll.next() {
+            println!("Timed out waiting for documents to finish processing");
+            break;
+        }
+    }
+    println!("{} {}", source["name"], document_counts(&source));
+    println!("{} {}", cloned["name"], document_counts(&cloned));
+}
@@ -0,0 +1,62 @@
+use clap;
+use serde_json::Value;
+use std;
+use std::io::{IsTerminal, Write, stdin, stdout};
+
+pub fn document_counts(collection: &Value) -> String {
+    let counts = &collection["document_counts"];
+    format!(
+        "{} available, {} processing, {} failed",
+        counts["available"].as_u64().unwrap_or(0),
+        counts["processing"].as_u64().unwrap_or(0),
+        counts["failed"].as_u64().unwrap_or(0)
+    )
+}
+
+pub fn describe_collection(collection: &Value) -> String {
+    format!(
+        "collection {} {}, {}",
+        collection["name"],
+        collection["collection_id"].as_str().unwrap_or(""),
+        document_counts(collection)
+    )
+}
+
+pub fn describe_configuration(configuration: &Value) -> String {
+    format!(
+        "configuration {} {}",
+        configuration["name"],
+        configuration["configuration_id"].as_str().unwrap_or("")
+    )
+}
+
+// Show what is about to be destroyed and insist on a typed "yes" before
+// returning. Exits when confirmation is refused, or when it cannot be
+// asked for because stdin is not a terminal and --yes was not given.
+pub fn confirm(matches: &clap::ArgMatches, action: &str, targets: &[String]) {
+    println!("About to {}:", action);
+    for target in targets {
+        println!("    {}", target);
+    }
+    if matches.is_present("yes") {
+        return;
+    }
+    if !stdin().is_terminal() {
+        println!(
+            "Refusing to {} without confirmation; stdin is not a terminal. \
+             Use --yes to skip confirmation.",
+            action
+        );
+        std::process::exit(1)
+    }
+    print!("Type \"yes\" to continue: ");
+    stdout().flush().expect("Failed to flush stdout");
+    let mut answer = String::new();
+    stdin().read_line(&mut answer).expect(
+        "Failed to read confirmation",
+    );
+    if answer.trim() != "yes" {
+        println!("Cancelled, nothing was changed.");
+        std::process::exit(1)
+    }
+}
@@ -1,17 +1,19 @@
 use clap;
 use info::discovery_service_info;
 use select::{select_configuration, writable_environment};
+use size::{create_with_size, parse_size};
+use template::render_configuration;
+use validate::validate_or_exit;
 
-use serde_json::{Value, from_reader, to_string_pretty};
+use serde_json::{Value, to_string_pretty};
 use std;
-use std::{thread, time};
 
 use wdsapi::collection;
 use wdsapi::collection::NewCollection;
 use wdsapi::common::Credentials;
 use wdsapi::configuration;
 use wdsapi::environment;
-use wdsapi::environment::NewEnvironment;
+use wait::{Poll, wait_for_status};
 
 // I suppose there is a standard library way to do this...
 fn optional_string(s: &Option<&str>) -> Option<String> {
@@ -21,16 +23,71 @@ fn optional_string(s: &Option<&str>) -> Option<String> {
     }
 }
 
+pub fn with_name<'a>(items: &'a [Value], name: &str) -> Option<&'a Value> {
+    items.iter().find(|item| item["name"].as_str() == Some(name))
+}
+
+// When something named `name` already exists, --if-not-exists prints it and
+// returns true, meaning there is nothing to create. Without that flag this
+// only warns about the duplicate name.
+pub fn already_exists(
+    matches: &clap::ArgMatches,
+    what: &str,
+    name: &str,
+    existing: Option<&Value>,
+) -> bool {
+    match existing {
+        Some(existing) if matches.is_present("if-not-exists") => {
+            println!(
+                "{}",
+                to_string_pretty(existing).expect(
+                    "Internal error: failed to format existing resource",
+                )
+            );
+            true
+        }
+        Some(_) => {
+            println!(
+                "Warning: creating another {} named {:?}; later commands \
+                 selecting it by name will fail",
+                what,
+                name
+            );
+            false
+        }
+        None => false,
+    }
+}
+
 pub fn create_environment(creds: &Credentials, matches: &clap::ArgMatches) {
-    let env_options = NewEnvironment {
-        name: matches.value_of("name").unwrap().to_string(),
-        description: optional_string(&matches.value_of("description")),
-        size: matches.value_of("size")
-                     .unwrap_or("0")
-                     .parse::<u64>()
-                     .unwrap(),
+    let name = matches.value_of("name").unwrap();
+    let environments = match environment::list(creds) {
+        Ok(listed) => {
+            listed["environments"]
+                .as_array()
+                .expect("Internal error: environments is not a list?")
+                .clone()
+        }
+        Err(e) => {
+            println!("Failed to get environment list {}", e);
+            std::process::exit(1)
+        }
     };
-    match environment::create(creds, &env_options) {
+    if already_exists(
+        matches,
+        "environment",
+        name,
+        with_name(&environments, name),
+    )
+    {
+        return;
+    }
+    match create_with_size(
+        creds,
+        name,
+        optional_string(&matches.value_of("description")),
+        parse_size(matches.value_of("size").unwrap_or("0")),
+    ) {
         Ok(response) => {
             println!(
                 "{}",
@@ -42,26 +99,15 @@ pub fn create_environment(creds: &Credentials, matches: &clap::ArgMatches) {
             let env_id = response["environment_id"].as_str().expect(
                 "Internal error: missing environment_id",
             );
-            if matches.is_present("wait") {
-                loop {
-                    thread::sleep(time::Duration::from_secs(1));
-                    match environment::detail(creds, env_id) {
-                        Ok(status) => {
-                            println!("{}", status["status"]);
-                            let s = status["status"].as_str().unwrap_or("");
-                            if "active" == s || "available" == s {
-                                break;
-                            }
-                        }
-                        Err(e) => {
-                            println!(
-                                "Continuing after environment status \
-                                      check failure {}",
-                                e
-                            )
-                        }
-                    }
-                }
+            if matches.is_present("wait") &&
+                !wait_for_status(
+                    &mut Poll::unbounded(matches),
+                    "Environment",
+                    &["active", "available"],
+                    || environment::detail(creds, env_id),
+                )
+            {
+                std::process::exit(1)
             }
         }
         Err(e) => {
@@ -75,6 +121,16 @@ pub fn create_collection(creds: Credentials, matches: &clap::ArgMatches) {
     let info = discovery_service_info(creds);
     let env_info = writable_environment(&info);
     let configuration = select_configuration(&env_info, matches);
+    let name = matches.value_of("name").unwrap();
+    if already_exists(
+        matches,
+        "collection",
+        name,
+        with_name(&env_info.collections, name),
+    )
+    {
+        return;
+    }
 
     let col_options = NewCollection {
         name: matches.value_of("name").unwrap().to_string(),
@@ -99,7 +155,26 @@ pub fn create_collection(creds: Credentials, matches: &clap::ArgMatches) {
                     "Internal error: failed to format \
                                   create_collection response",
                 )
-            )
+            );
+            let col_id = response["collection_id"].as_str().expect(
+                "Internal error: missing collection_id",
+            );
+            if matches.is_present("wait") &&
+                !wait_for_status(
+                    &mut Poll::new(matches),
+                    "Collection",
+                    &["active"],
+                    || {
+                        collection::detail(
+                            &info.creds,
+                            &env_info.environment_id,
+                            col_id,
+                        )
+                    },
+                )
+            {
+                std::process::exit(1)
+            }
         }
         Err(e) => {
             println!("Failed to create collection {}", e);
@@ -109,16 +184,41 @@ pub fn create_collection(creds: Credentials, matches: &clap::ArgMatches) {
 }
 
 pub fn create_configuration(creds: Credentials, matches: &clap::ArgMatches) {
+    let config = render_configuration(matches);
+    if matches.is_present("render-only") {
+        println!(
+            "{}",
+            to_string_pretty(&config).expect(
+                "Internal error: failed to format configuration",
+            )
+        );
+        return;
+    }
+    validate_or_exit(&config, matches);
+
     let info = discovery_service_info(creds);
     let env_info = writable_environment(&info);
-    let env_id = env_info.environment_id;
-    let config_filename =
-        matches.value_of("configuration").unwrap().to_string();
-    let config_file = std::fs::File::open(config_filename).expect(
-        "Failed to read configuration JSON file",
-    );
-    let config: Value =
-        from_reader(config_file).expect("Failed to parse configuration JSON");
+    let env_id = env_info.environment_id.clone();
+    let name = config["name"].as_str().unwrap_or("");
+    let existing = match with_name(&env_info.configurations, name) {
+        Some(listed) => {
+            match configuration::detail(
+                &info.creds,
+                &env_id,
+                listed["configuration_id"].as_str().unwrap_or(""),
+            ) {
+                Ok(response) => Some(response),
+                Err(e) => {
+                    println!("Failed to lookup configuration {}", e);
+                    std::process::exit(1)
+                }
+            }
+        }
+        None => None,
+    };
+    if already_exists(matches, "configuration", name, existing.as_ref()) {
+        return;
+    }
 
     match configuration::create(&info.creds, &env_id, &config) {
         Ok(response) => {
@@ -1,17 +1,66 @@
+use backup::backup_collection;
+use bulk::{Context, Pool, final_report, thread_count, with_retry};
 use clap;
+use confirm::{confirm, describe_collection, describe_configuration,
+              document_counts};
+use glob::Pattern;
 use info::discovery_service_info;
-use rayon::prelude::*;
+use query::{all_documents, each_notice, each_result};
+use regex::Regex;
 use select::{select_collection, select_configuration, writable_environment};
 use serde_json::{Value, to_string_pretty};
+use std;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader, stdin};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use wdsapi::collection;
-use wdsapi::common::{ApiError, Credentials};
+use wdsapi::common::{ApiError, Credentials, QueryParams};
 use wdsapi::configuration;
 use wdsapi::document;
 use wdsapi::environment;
+use wait::{Poll, wait_until_gone};
+
+// With --wait, poll until each deleted resource is gone, exiting
+// non-zero when the timeout passes first.
+fn wait_for_deletes<F>(
+    matches: &clap::ArgMatches,
+    what: &str,
+    ids: &[String],
+    detail: F,
+) where
+    F: Fn(&str) -> Result<Value, ApiError>,
+{
+    if !matches.is_present("wait") {
+        return;
+    }
+    // One --timeout covers them all, but each starts polling quickly.
+    let overall = Poll::new(matches);
+    for id in ids {
+        let description = format!("{} {}", what, id);
+        let mut poll = overall.restarted();
+        if !wait_until_gone(&mut poll, &description, || detail(id)) {
+            std::process::exit(1)
+        }
+    }
+}
 
 pub fn delete_environment(creds: Credentials, matches: &clap::ArgMatches) {
     let info = discovery_service_info(creds);
-    let env_id = writable_environment(&info).environment_id;
+    let env_info = writable_environment(&info);
+    let env_id = env_info.environment_id.clone();
+
+    let mut targets = vec![
+        format!(
+            "environment {} {}",
+            env_info.environment["name"],
+            env_id
+        ),
+    ];
+    targets.extend(env_info.configurations.iter().map(describe_configuration));
+    targets.extend(env_info.collections.iter().map(describe_collection));
+    confirm(matches, "delete the writable environment", &targets);
 
     match environment::delete(&info.creds, &env_id) {
         Ok(response) => {
@@ -21,7 +70,10 @@ pub fn delete_environment(creds: Credentials, matches: &clap::ArgMatches) {
                     "Internal error: failed to format \
                                   delete_environment response",
                 )
-            )
+            );
+            wait_for_deletes(matches, "environment", &[env_id], |id| {
+                environment::detail(&info.creds, id)
+            })
         }
         Err(e) => println!("Failed to delete environment {}", e),
     }
@@ -31,7 +83,7 @@ pub fn delete_one_collection(
     creds: &Credentials,
     env_id: &str,
     collection_id: &str,
-) {
+) -> bool {
     match collection::delete(creds, env_id, collection_id) {
         Ok(response) => {
             println!(
@@ -40,39 +92,146 @@ pub fn delete_one_collection(
                     "Internal error: failed to format \
                                   delete_collection response",
                 )
-            )
+            );
+            true
+        }
+        Err(e) => {
+            println!("Failed to delete collection {}", e);
+            false
+        }
+    }
+}
+
+// Delete a collection, first backing it up when --backup is given. The
+// collection is kept when the backup fails. Returns true when the
+// collection was deleted.
+fn backup_and_delete_collection(
+    creds: &Credentials,
+    env_id: &str,
+    collection: &Value,
+    matches: &clap::ArgMatches,
+) -> bool {
+    if let Some(dir) = matches.value_of("backup") {
+        if backup_collection(creds, env_id, collection, dir).is_none() {
+            println!(
+                "Not deleting collection {}, the backup failed",
+                collection["name"]
+            );
+            return false;
         }
-        Err(e) => println!("Failed to delete collection {}", e),
     }
+    delete_one_collection(
+        creds,
+        env_id,
+        collection["collection_id"].as_str().unwrap(),
+    )
+}
+
+// The --all filters; a collection must pass every filter given.
+fn collection_filter(
+    matches: &clap::ArgMatches,
+) -> Box<dyn Fn(&Value) -> bool> {
+    let glob = matches.value_of("name-glob").map(|g| {
+        Pattern::new(g).expect("Invalid --name-glob pattern")
+    });
+    let regex = matches.value_of("name-regex").map(|r| {
+        Regex::new(r).expect("Invalid --name-regex expression")
+    });
+    // Created timestamps look like "2017-06-19T14:12:55.806Z", so an ISO
+    // 8601 date or UTC timestamp compares correctly as a string.
+    let created_before = matches.value_of("created-before").map(|d| {
+        let iso_8601 = Regex::new(concat!(
+            r"^\d{4}-(0[1-9]|1[0-2])-(0[1-9]|[12]\d|3[01])",
+            r"(T([01]\d|2[0-3]):[0-5]\d(:[0-5]\d(\.\d+)?)?Z?)?$"
+        )).expect("Internal error: invalid date expression");
+        if !iso_8601.is_match(d) {
+            println!(
+                "Expected an ISO 8601 date or UTC timestamp for \
+                 --created-before, like 2017-06-30 or \
+                 2017-06-30T12:00:00Z, not {}",
+                d
+            );
+            std::process::exit(1)
+        }
+        d.to_string()
+    });
+    let empty_only = matches.is_present("empty-only");
+    let failed_over = matches.value_of("failed-over").map(|n| {
+        n.parse::<u64>().expect("Failed-over must be an integer")
+    });
+    Box::new(move |collection: &Value| {
+        let name = collection["name"].as_str().unwrap_or("");
+        let counts = &collection["document_counts"];
+        let failed = counts["failed"].as_u64().unwrap_or(0);
+        glob.as_ref().map_or(true, |g| g.matches(name)) &&
+            regex.as_ref().map_or(true, |r| r.is_match(name)) &&
+            created_before.as_ref().map_or(true, |date| {
+                collection["created"].as_str().unwrap_or("") < date.as_str()
+            }) &&
+            (!empty_only ||
+                 counts["available"].as_u64().unwrap_or(0) +
+                     counts["processing"].as_u64().unwrap_or(0) +
+                     failed == 0) &&
+            failed_over.map_or(true, |n| failed > n)
+    })
 }
 
 pub fn delete_collection(creds: Credentials, matches: &clap::ArgMatches) {
     let info = discovery_service_info(creds);
     let env_info = writable_environment(&info);
     let env_id = writable_environment(&info).environment_id;
-    if matches.is_present("all") {
-        for collection in env_info.collections {
-            delete_one_collection(
-                &info.creds,
-                &env_id,
-                collection["collection_id"].as_str().unwrap(),
-            )
+    let collections: Vec<Value> = if matches.is_present("all") {
+        let filter = collection_filter(matches);
+        let collections: Vec<Value> = env_info.collections
+                                              .into_iter()
+                                              .filter(|c| filter(c))
+                                              .collect();
+        if collections.is_empty() {
+            println!("No collections to delete");
+            return;
         }
+        let targets: Vec<String> =
+            collections.iter().map(describe_collection).collect();
+        confirm(
+            matches,
+            &format!("delete {} collections", collections.len()),
+            &targets,
+        );
+        collections
     } else {
         let collection = select_collection(&env_info, matches);
-        delete_one_collection(
-            &info.creds,
-            &env_id,
-            collection["collection_id"].as_str().unwrap(),
-        )
-    }
+        confirm(
+            matches,
+            "delete a collection",
+            &[describe_collection(&collection)],
+        );
+        vec![collection]
+    };
+
+    let deleted: Vec<String> = collections
+        .iter()
+        .filter(|collection| {
+            backup_and_delete_collection(
+                &info.creds,
+                &env_id,
+                collection,
+                matches,
+            )
+        })
+        .map(|collection| {
+            collection["collection_id"].as_str().unwrap_or("").to_string()
+        })
+        .collect();
+    wait_for_deletes(matches, "collection", &deleted, |id| {
+        collection::detail(&info.creds, &env_id, id)
+    })
 }
 
 pub fn delete_one_configuration(
     creds: &Credentials,
     env_id: &str,
     configuration_id: &str,
-) {
+) -> bool {
     match configuration::delete(creds, env_id, configuration_id) {
         Ok(response) => {
             println!(
@@ -81,9 +240,13 @@ pub fn delete_one_configuration(
                     "Internal error: failed to format \
                                   delete_configuration response",
                 )
-            )
+            );
+            true
+        }
+        Err(e) => {
+            println!("Failed to delete configuration {}", e);
+            false
         }
-        Err(e) => println!("Failed to delete configuration {}", e),
     }
 }
 
@@ -91,54 +254,292 @@ pub fn delete_configuration(creds: Credentials, matches: &clap::ArgMatches) {
     let info = discovery_service_info(creds);
     let env_info = writable_environment(&info);
     let env_id = writable_environment(&info).environment_id;
-    if matches.is_present("all") {
-        for configuration in env_info.configurations {
-            let configuration_id =
-                configuration["configuration_id"].as_str().expect(
-                    "Internal error: missing configuration_id",
-                );
-            delete_one_configuration(&info.creds, &env_id, configuration_id)
+    let configurations: Vec<Value> = if matches.is_present("all") {
+        if env_info.configurations.is_empty() {
+            println!("No configurations to delete");
+            return;
         }
+        let targets: Vec<String> = env_info.configurations
+                                           .iter()
+                                           .map(describe_configuration)
+                                           .collect();
+        confirm(matches, "delete every configuration", &targets);
+        env_info.configurations.clone()
     } else {
         let configuration = select_configuration(&env_info, matches);
+        confirm(
+            matches,
+            "delete a configuration",
+            &[describe_configuration(&configuration)],
+        );
+        vec![configuration]
+    };
+
+    let mut deleted = Vec::new();
+    for configuration in configurations {
         let configuration_id =
             configuration["configuration_id"].as_str().expect(
                 "Internal error: missing configuration_id",
             );
-        delete_one_configuration(&info.creds, &env_id, configuration_id)
+        if delete_one_configuration(&info.creds, &env_id, configuration_id) {
+            deleted.push(configuration_id.to_string())
+        }
     }
+    wait_for_deletes(matches, "configuration", &deleted, |id| {
+        configuration::detail(&info.creds, &env_id, id)
+    })
+}
+
+fn delete_document_with_retry(context: &Context, doc_id: &str) -> bool {
+    with_retry(context, doc_id, "delete document", || {
+        document::delete(
+            &context.creds,
+            &context.env_id,
+            &context.col_id,
+            doc_id,
+        )
+    })
+}
+
+// Delete documents through a pool of threads. Returns the time taken and
+// the ids the service accepted a delete for; ids given up on are left out.
+fn delete_documents(
+    context: &Arc<Context>,
+    matches: &clap::ArgMatches,
+    document_ids: &[String],
+) -> (Duration, Vec<String>) {
+    let deleted = Arc::new(Mutex::new(Vec::new()));
+    let worker_deleted = deleted.clone();
+    let pool = Pool::start(
+        context.clone(),
+        thread_count(matches),
+        move |context, doc_id| if delete_document_with_retry(context, doc_id) {
+            worker_deleted
+                .lock()
+                .expect("Internal error: poisoned lock")
+                .push(doc_id.to_string())
+        },
+    );
+    for doc_id in document_ids {
+        pool.push(doc_id.clone());
+    }
+    let duration = pool.finish();
+    let deleted = deleted.lock()
+                         .expect("Internal error: poisoned lock")
+                         .clone();
+    (duration, deleted)
+}
+
+// Document ids, one per line, from a file or from stdin when the name is
+// "-".
+fn read_document_ids(name: &str) -> Vec<String> {
+    let reader: Box<dyn BufRead> = if name == "-" {
+        Box::new(BufReader::new(stdin()))
+    } else {
+        Box::new(BufReader::new(File::open(name).expect(
+            "Failed to open document id file",
+        )))
+    };
+    reader.lines()
+          .map(|line| line.expect("Failed to read document id"))
+          .map(|line| line.trim().to_string())
+          .filter(|line| !line.is_empty())
+          .collect()
 }
 
 pub fn delete_document(creds: Credentials, matches: &clap::ArgMatches) {
+    let info = discovery_service_info(creds);
+    let env_info = writable_environment(&info);
+    let collection = select_collection(&env_info, matches);
+    let col_id = collection["collection_id"].as_str().expect(
+        "Internal error: missing collection_id",
+    );
+
+    let mut document_ids: Vec<String> = match matches.values_of(
+        "document_id",
+    ) {
+        Some(ids) => ids.map(|id| id.to_string()).collect(),
+        None => Vec::new(),
+    };
+    if let Some(name) = matches.value_of("ids-from") {
+        // Once the ids have been read to the end of stdin there is nothing
+        // left to read a typed confirmation from.
+        if name == "-" && !matches.is_present("yes") {
+            println!(
+                "Reading document ids from stdin leaves no way to type a \
+                 confirmation. Use --yes with --ids-from -."
+            );
+            std::process::exit(1)
+        }
+        document_ids.extend(read_document_ids(name));
+    }
+    confirm(
+        matches,
+        &format!("delete {} documents", document_ids.len()),
+        &[describe_collection(&collection)],
+    );
+
+    let context = Arc::new(Context::new(
+        &info.creds,
+        &env_info.environment_id,
+        col_id,
+        matches,
+    ));
+    let (duration, deleted) =
+        delete_documents(&context, matches, &document_ids);
+    final_report(duration, &context, "Deleted");
+    wait_for_deletes(matches, "document", &deleted, |id| {
+        document::detail(&info.creds, &env_info.environment_id, col_id, id)
+    })
+}
+
+fn document_ids_page(offset: u64, count: u64) -> QueryParams {
+    QueryParams {
+        return_hierarchy: Some("id".to_string()),
+        ..all_documents(offset, count)
+    }
+}
+
+fn error_notices_page(offset: u64, count: u64) -> QueryParams {
+    QueryParams {
+        filter: Some("severity::error".to_string()),
+        ..all_documents(offset, count)
+    }
+}
+
+// Every document the collection counts, including those that failed.
+fn remaining_documents(collection: &Value) -> u64 {
+    let counts = &collection["document_counts"];
+    counts["available"].as_u64().unwrap_or(0) +
+        counts["processing"].as_u64().unwrap_or(0) +
+        counts["failed"].as_u64().unwrap_or(0)
+}
+
+// Documents that failed are never returned by a query; their ids come
+// from the error notices the service kept for them.
+fn failed_document_ids(
+    creds: &Credentials,
+    env_id: &str,
+    col_id: &str,
+) -> Vec<String> {
+    let mut document_ids = Vec::new();
+    each_notice(creds, env_id, col_id, error_notices_page, |notice| {
+        if let Some(id) = notice["document_id"].as_str() {
+            if !document_ids.iter().any(|known| known == id) {
+                document_ids.push(id.to_string())
+            }
+        }
+    }).expect("Failed to query collection notices");
+    document_ids
+}
+
+pub fn purge_collection(creds: Credentials, matches: &clap::ArgMatches) {
     let info = discovery_service_info(creds);
     let env_info = writable_environment(&info);
     let collection = select_collection(&env_info, matches);
     let env_id = env_info.environment_id;
-    let col_id = collection["collection_id"].as_str().unwrap_or("");
-
-    // I didn't figure out how to use the matches directly...
-    let document_ids: Vec<&str> =
-        matches.values_of("document_id")
-               .expect("Internal error: missing document_id")
-               .collect();
-
-    let document_statuses: Vec<Result<Value, ApiError>> =
-        document_ids.par_iter()
-                    .map(|doc_id| {
-            document::delete(&info.creds, &env_id, col_id, doc_id)
-        })
-                    .collect();
+    let col_id = collection["collection_id"].as_str().expect(
+        "Internal error: missing collection_id",
+    );
+    confirm(
+        matches,
+        "delete every document in a collection",
+        &[describe_collection(&collection)],
+    );
 
-    for doc in document_statuses {
-        match doc {
-            Ok(response) => {
+    let context =
+        Arc::new(Context::new(&info.creds, &env_id, col_id, matches));
+    // Each wait for the service gets the full --timeout.
+    let mut waiting: Option<Poll> = None;
+    let mut duration = Duration::new(0, 0);
+    let mut deleted: HashSet<String> = HashSet::new();
+    // A query only pages through so many results, so gather and delete
+    // the documents in rounds. Deletes are processed asynchronously, so a
+    // query can still return documents deleted in an earlier round; when
+    // that is all it returns, wait for the service to catch up.
+    loop {
+        let mut document_ids = Vec::new();
+        let matching_results = each_result(
+            &info.creds,
+            &env_id,
+            col_id,
+            document_ids_page,
+            |result| if let Some(id) = result["id"].as_str() {
+                document_ids.push(id.to_string())
+            },
+        ).expect("Failed to query collection");
+        let returned = document_ids.len() as u64;
+        document_ids.retain(|id| !deleted.contains(id));
+        if document_ids.is_empty() && returned >= matching_results {
+            document_ids = failed_document_ids(&info.creds, &env_id, col_id);
+            document_ids.retain(|id| !deleted.contains(id));
+            if document_ids.is_empty() {
+                break;
+            }
+        }
+        if document_ids.is_empty() {
+            let poll = waiting.get_or_insert_with(|| Poll::new(matches));
+            if !poll.next() {
                 println!(
-                    "{}",
-                    to_string_pretty(&response).expect(
-                        "Internal error: failed to format document::delete response")
+                    "Timed out waiting for deletes to be processed with {} \
+                     documents left",
+                    matching_results
+                );
+                std::process::exit(1)
+            }
+            continue;
+        }
+        println!(
+            "Deleting {} of {} remaining documents",
+            document_ids.len(),
+            matching_results
+        );
+        let (round_duration, round_deleted) =
+            delete_documents(&context, matches, &document_ids);
+        duration += round_duration;
+        if context.failures() > 0 || round_deleted.is_empty() {
+            final_report(duration, &context, "Deleted");
+            println!("Stopping; some documents could not be deleted");
+            std::process::exit(1)
+        }
+        deleted.extend(round_deleted);
+        waiting = None;
+    }
+    final_report(duration, &context, "Deleted");
+
+    // Poll until the counts show every delete processed; with --no-wait,
+    // just show where they are now.
+    let mut poll = Poll::new(matches);
+    let mut last_counts = String::new();
+    loop {
+        match collection::detail(&info.creds, &env_id, col_id) {
+            Ok(detail) => {
+                let counts = document_counts(&detail);
+                if counts != last_counts {
+                    println!("{}", counts);
+                    last_counts = counts;
+                }
+                if remaining_documents(&detail) == 0 {
+                    break;
+                }
+                if matches.is_present("no-wait") {
+                    println!(
+                        "The service is still processing the deletes; \
+                         show-collection reports the counts"
+                    );
+                    return;
+                }
+            }
+            Err(e) => {
+                println!(
+                    "Continuing after collection status check failure {}",
+                    e
                 )
             }
-            Err(e) => println!("Failed to lookup document {}", e),
+        }
+        if !poll.next() {
+            println!("Timed out waiting for the deletes to be processed");
+            std::process::exit(1)
         }
     }
 }
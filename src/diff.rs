@@ -0,0 +1,188 @@
+use clap;
+use info::{EnvironmentInfo, discovery_service_info};
+use select::{configuration_with_selector, writable_environment};
+use serde_json::{Value, to_string};
+use std;
+use std::borrow::Borrow;
+use std::collections::BTreeSet;
+use template::read_document;
+use wdsapi::common::Credentials;
+use wdsapi::configuration;
+
+// Fields the service sets on a configuration; never worth comparing.
+const SERVER_MANAGED: &[&str] = &["configuration_id", "created", "updated"];
+
+pub struct Difference {
+    pub path: String,
+    pub old: Option<Value>,
+    pub new: Option<Value>,
+}
+
+pub fn without_server_fields(value: &Value) -> Value {
+    let mut value = value.clone();
+    if let Some(fields) = value.as_object_mut() {
+        for field in SERVER_MANAGED {
+            fields.remove(*field);
+        }
+    }
+    value
+}
+
+fn diff_at(path: &str, old: &Value, new: &Value, out: &mut Vec<Difference>) {
+    match (old, new) {
+        (&Value::Object(ref old_fields), &Value::Object(ref new_fields)) => {
+            let keys: BTreeSet<&String> =
+                old_fields.keys().chain(new_fields.keys()).collect();
+            for key in keys {
+                let key_path = if path.is_empty() {
+                    key.to_string()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                match (old_fields.get(key), new_fields.get(key)) {
+                    (Some(o), Some(n)) => diff_at(&key_path, o, n, out),
+                    (o, n) => {
+                        out.push(Difference {
+                            path: key_path,
+                            old: o.cloned(),
+                            new: n.cloned(),
+                        })
+                    }
+                }
+            }
+        }
+        (&Value::Array(ref old_items), &Value::Array(ref new_items)) => {
+            for i in 0..old_items.len().max(new_items.len()) {
+                let item_path = format!("{}[{}]", path, i);
+                match (old_items.get(i), new_items.get(i)) {
+                    (Some(o), Some(n)) => diff_at(&item_path, o, n, out),
+                    (o, n) => {
+                        out.push(Difference {
+                            path: item_path,
+                            old: o.cloned(),
+                            new: n.cloned(),
+                        })
+                    }
+                }
+            }
+        }
+        _ => {
+            if old != new {
+                out.push(Difference {
+                    path: path.to_string(),
+                    old: Some(old.clone()),
+                    new: Some(new.clone()),
+                })
+            }
+        }
+    }
+}
+
+// Structural differences between two JSON documents. Object keys are
+// compared without regard to order, array items by position.
+pub fn diff(old: &Value, new: &Value) -> Vec<Difference> {
+    let mut out = Vec::new();
+    diff_at("", old, new, &mut out);
+    out
+}
+
+fn compact(value: &Value) -> String {
+    to_string(value).unwrap_or_default()
+}
+
+pub fn print_differences<D>(differences: &[D])
+where
+    D: Borrow<Difference>,
+{
+    for d in differences {
+        let d = d.borrow();
+        match (&d.old, &d.new) {
+            (&Some(ref o), &Some(ref n)) => {
+                println!("~ {}: {} → {}", d.path, compact(o), compact(n))
+            }
+            (&None, &Some(ref n)) => println!("+ {}: {}", d.path, compact(n)),
+            (&Some(ref o), &None) => println!("- {}: {}", d.path, compact(o)),
+            (&None, &None) => (),
+        }
+    }
+}
+
+// The configuration for a selector, or the contents of a local file.
+fn configuration_for(
+    creds: &Credentials,
+    env_info: &EnvironmentInfo,
+    selector: &str,
+) -> Value {
+    if selector.starts_with("file:") {
+        return read_document(&selector[5..]);
+    }
+    let selected = match configuration_with_selector(env_info, selector) {
+        Some(selected) => selected,
+        None => return read_document(selector),
+    };
+    let configuration_id = match selected["configuration_id"].as_str() {
+        Some(id) => id,
+        None => {
+            println!("No configuration matched {}", selector);
+            std::process::exit(1)
+        }
+    };
+    match configuration::detail(
+        creds,
+        &env_info.environment_id,
+        configuration_id,
+    ) {
+        Ok(response) => response,
+        Err(e) => {
+            println!("Failed to lookup configuration {}", e);
+            std::process::exit(1)
+        }
+    }
+}
+
+// The parts of a configuration worth calling out on their own.
+const SECTIONS: &[&str] = &["enrichments", "conversions", "normalizations"];
+
+fn in_section(path: &str, section: &str) -> bool {
+    path.starts_with(section) &&
+        path[section.len()..]
+            .chars()
+            .next()
+            .map_or(true, |c| c == '.' || c == '[')
+}
+
+pub fn diff_configuration(creds: Credentials, matches: &clap::ArgMatches) {
+    let info = discovery_service_info(creds);
+    let env_info = writable_environment(&info);
+    let left_selector = matches.value_of("left").unwrap();
+    let right_selector = matches.value_of("right").unwrap();
+    let left = configuration_for(&info.creds, &env_info, left_selector);
+    let right = configuration_for(&info.creds, &env_info, right_selector);
+
+    let differences =
+        diff(&without_server_fields(&left), &without_server_fields(&right));
+    if differences.is_empty() {
+        println!("No differences");
+        return;
+    }
+    println!("--- {} {}", left_selector, left["name"]);
+    println!("+++ {} {}", right_selector, right["name"]);
+    for section in SECTIONS {
+        let in_this: Vec<&Difference> = differences
+            .iter()
+            .filter(|d| in_section(&d.path, section))
+            .collect();
+        if !in_this.is_empty() {
+            println!("\n{}: {} changes", section, in_this.len());
+            print_differences(&in_this);
+        }
+    }
+    let others: Vec<&Difference> = differences
+        .iter()
+        .filter(|d| !SECTIONS.iter().any(|s| in_section(&d.path, s)))
+        .collect();
+    if !others.is_empty() {
+        println!("\nother: {} changes", others.len());
+        print_differences(&others);
+    }
+}
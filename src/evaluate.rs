@@ -0,0 +1,486 @@
+use clap;
+use info::discovery_service_info;
+use rayon::prelude::*;
+use render::print_rows;
+use select::{collection_with_selector, select_collection, writable_environment};
+use serde_json::Value;
+use std;
+use std::collections::BTreeMap;
+use std::thread;
+use std::time::Duration;
+use template::read_document;
+use wdsapi::common::{Credentials, QueryParams};
+use wdsapi::query;
+
+// One judged query: what to ask, and how relevant each document is. Any
+// grade above zero counts as relevant.
+struct Judgment {
+    label: String,
+    query: Option<String>,
+    natural_language_query: Option<String>,
+    filter: Option<String>,
+    grades: BTreeMap<String, f64>,
+}
+
+struct Scores {
+    precision: f64,
+    recall: f64,
+    reciprocal_rank: f64,
+    ndcg: f64,
+}
+
+fn optional_string(value: &Value) -> Option<String> {
+    value.as_str().map(|s| s.to_string())
+}
+
+// "relevant" is either a list of document ids, all graded 1, or a map of
+// document id to grade.
+fn grades(entry: &Value) -> BTreeMap<String, f64> {
+    let relevant = &entry["relevant"];
+    let mut grades = BTreeMap::new();
+    if let Some(ids) = relevant.as_array() {
+        for id in ids.iter().filter_map(|id| id.as_str()) {
+            grades.insert(id.to_string(), 1.0);
+        }
+    } else if let Some(graded) = relevant.as_object() {
+        for (id, grade) in graded {
+            grades.insert(id.to_string(), grade.as_f64().unwrap_or(0.0));
+        }
+    }
+    grades
+}
+
+// The judgments file is a list of queries, or an object with the list
+// under "queries".
+fn read_judgments(filename: &str) -> Vec<Judgment> {
+    let document = read_document(filename);
+    let entries = match document.as_array() {
+        Some(entries) => entries.clone(),
+        None => {
+            match document["queries"].as_array() {
+                Some(entries) => entries.clone(),
+                None => {
+                    println!("Expected a list of queries in {}", filename);
+                    std::process::exit(1)
+                }
+            }
+        }
+    };
+    entries.iter()
+           .map(|entry| {
+        let query = optional_string(&entry["query"]);
+        let natural_language_query =
+            optional_string(&entry["natural_language_query"]);
+        let label = query.clone()
+                         .or(natural_language_query.clone())
+                         .unwrap_or_else(|| {
+            println!(
+                "Each judged query needs a query or a \
+                 natural_language_query"
+            );
+            std::process::exit(1)
+        });
+        Judgment {
+            label: label,
+            query: query,
+            natural_language_query: natural_language_query,
+            filter: optional_string(&entry["filter"]),
+            grades: grades(entry),
+        }
+    })
+           .collect()
+}
+
+fn discount(rank: usize) -> f64 {
+    ((rank + 2) as f64).log2()
+}
+
+fn gain(grade: f64) -> f64 {
+    2f64.powf(grade) - 1.0
+}
+
+// Scores for the ids a query returned, best first, against its judgment.
+fn score(judgment: &Judgment, returned: &[String], k: usize) -> Scores {
+    let top = &returned[..returned.len().min(k)];
+    let grade = |id: &String| *judgment.grades.get(id).unwrap_or(&0.0);
+    let relevant_total =
+        judgment.grades.values().filter(|&&g| g > 0.0).count();
+    let relevant_found = top.iter().filter(|id| grade(id) > 0.0).count();
+    let first_relevant = top.iter().position(|id| grade(id) > 0.0);
+
+    let dcg: f64 = top.iter()
+                      .enumerate()
+                      .map(|(rank, id)| gain(grade(id)) / discount(rank))
+                      .sum();
+    let mut ideal: Vec<f64> = judgment.grades.values().cloned().collect();
+    ideal.sort_by(|a, b| b.partial_cmp(a).unwrap());
+    let idcg: f64 = ideal.iter()
+                         .take(k)
+                         .enumerate()
+                         .map(|(rank, g)| gain(*g) / discount(rank))
+                         .sum();
+    Scores {
+        precision: relevant_found as f64 / k as f64,
+        recall: if relevant_total == 0 {
+            0.0
+        } else {
+            relevant_found as f64 / relevant_total as f64
+        },
+        reciprocal_rank: first_relevant.map_or(0.0, |r| 1.0 / (r + 1) as f64),
+        ndcg: if idcg == 0.0 { 0.0 } else { dcg / idcg },
+    }
+}
+
+fn query_params(judgment: &Judgment, k: usize) -> QueryParams {
+    QueryParams {
+        filter: judgment.filter.clone(),
+        query: judgment.query.clone(),
+        natural_language_query: judgment.natural_language_query.clone(),
+        passages: Some(false),
+        aggregation: None,
+        count: k as u64,
+        return_hierarchy: Some("id".to_string()),
+        offset: None,
+        sort: None,
+    }
+}
+
+// The ids a judged query returns, best first, or None when the query
+// still fails after `retries` retries. Retries back off from one second.
+fn returned_ids(
+    creds: &Credentials,
+    env_id: &str,
+    col_id: &str,
+    judgment: &Judgment,
+    k: usize,
+    retries: u32,
+) -> Option<Vec<String>> {
+    let mut backoff = Duration::from_secs(1);
+    for attempt in 0..retries + 1 {
+        match query::query(creds, env_id, col_id, query_params(judgment, k)) {
+            Ok(response) => {
+                return Some(
+                    response["results"]
+                        .as_array()
+                        .map(|results| {
+                        results.iter()
+                               .filter_map(|r| optional_string(&r["id"]))
+                               .collect()
+                    })
+                        .unwrap_or_default(),
+                )
+            }
+            Err(e) => {
+                if attempt < retries {
+                    println!("Query {:?} retry after {}", judgment.label, e);
+                    thread::sleep(backoff);
+                    backoff *= 2;
+                } else {
+                    println!("Query {:?} failed {}", judgment.label, e)
+                }
+            }
+        }
+    }
+    None
+}
+
+// Run every judged query against one collection, `threads` at a time.
+// A query that fails is None, rather than scored as returning nothing.
+fn evaluate_collection(
+    creds: &Credentials,
+    env_id: &str,
+    col_id: &str,
+    judgments: &[Judgment],
+    k: usize,
+    threads: usize,
+    retries: u32,
+) -> Vec<Option<Scores>> {
+    let mut all = Vec::new();
+    for batch in judgments.chunks(threads) {
+        let scores: Vec<Option<Scores>> =
+            batch.par_iter()
+                 .map(|judgment| {
+            returned_ids(creds, env_id, col_id, judgment, k, retries)
+                .map(|returned| score(judgment, &returned, k))
+        })
+                 .collect();
+        all.extend(scores);
+    }
+    all
+}
+
+fn metrics(scores: &Scores) -> [f64; 4] {
+    [
+        scores.precision,
+        scores.recall,
+        scores.reciprocal_rank,
+        scores.ndcg,
+    ]
+}
+
+fn mean(all: &[&Scores]) -> [f64; 4] {
+    let mut sums = [0.0; 4];
+    for scores in all {
+        for (sum, metric) in sums.iter_mut().zip(metrics(scores).iter()) {
+            *sum += *metric
+        }
+    }
+    let n = all.len().max(1) as f64;
+    [sums[0] / n, sums[1] / n, sums[2] / n, sums[3] / n]
+}
+
+// The scores of just the queries listed in `succeeded`.
+fn scored<'a>(
+    all: &'a [Option<Scores>],
+    succeeded: &[usize],
+) -> Vec<&'a Scores> {
+    succeeded.iter().filter_map(|&i| all[i].as_ref()).collect()
+}
+
+// The metrics as table cells, or "failed" in each for a failed query.
+fn cells(scores: &Option<Scores>) -> Vec<String> {
+    match *scores {
+        Some(ref scores) => metrics(scores).iter().map(|m| fixed(*m)).collect(),
+        None => vec!["failed".to_string(); 4],
+    }
+}
+
+fn fixed(value: f64) -> String {
+    format!("{:.3}", value)
+}
+
+fn signed(value: f64) -> String {
+    format!("{:+.3}", value)
+}
+
+fn collection_name(collection: &Value) -> String {
+    collection["name"].as_str().unwrap_or("").to_string()
+}
+
+pub fn evaluate(creds: Credentials, matches: &clap::ArgMatches) {
+    let judgments = read_judgments(matches.value_of("judgments").unwrap());
+    let k: usize = match matches.value_of("k").unwrap_or("10").parse() {
+        Ok(k) if k > 0 => k,
+        _ => {
+            println!("k must be a positive integer");
+            std::process::exit(1)
+        }
+    };
+    let threads: usize =
+        match matches.value_of("threads").unwrap_or("4").parse() {
+            Ok(threads) if threads > 0 => threads,
+            _ => {
+                println!("Threads must be a positive integer");
+                std::process::exit(1)
+            }
+        };
+    let retries: u32 =
+        matches.value_of("retries").unwrap_or("2").parse().expect(
+            "Retries must be an integer",
+        );
+    let info = discovery_service_info(creds);
+    let env_info = writable_environment(&info);
+    let env_id = &env_info.environment_id;
+    let collection = select_collection(&env_info, matches);
+    let compared = matches.value_of("compare").map(|selector| {
+        match collection_with_selector(&env_info, selector) {
+            Some(compared) => compared,
+            None => {
+                println!(
+                    "Expected newest, oldest, name:<name> or id:<id> for \
+                     --compare, not {}",
+                    selector
+                );
+                std::process::exit(1)
+            }
+        }
+    });
+
+    let names = [
+        format!("P@{}", k),
+        format!("R@{}", k),
+        "RR".to_string(),
+        format!("NDCG@{}", k),
+    ];
+    let base = evaluate_collection(
+        &info.creds,
+        env_id,
+        collection["collection_id"].as_str().unwrap(),
+        &judgments,
+        k,
+        threads,
+        retries,
+    );
+    let other = compared.as_ref().map(|compared| {
+        evaluate_collection(
+            &info.creds,
+            env_id,
+            compared["collection_id"].as_str().unwrap(),
+            &judgments,
+            k,
+            threads,
+            retries,
+        )
+    });
+
+    // Per query: each metric, and with --compare, each metric for both
+    // collections.
+    let mut headings = vec!["query".to_string()];
+    match compared {
+        None => headings.extend(names.iter().cloned()),
+        Some(ref compared) => {
+            let a = collection_name(&collection);
+            let b = collection_name(compared);
+            for name in &names {
+                headings.push(format!("{} {}", name, a));
+                headings.push(format!("{} {}", name, b));
+            }
+        }
+    }
+    let rows: Vec<Vec<String>> =
+        judgments.iter()
+                 .enumerate()
+                 .map(|(i, judgment)| {
+        let mut row = vec![judgment.label.clone()];
+        let a = cells(&base[i]);
+        match other {
+            None => row.extend(a),
+            Some(ref other) => {
+                for (x, y) in a.into_iter().zip(cells(&other[i])) {
+                    row.push(x);
+                    row.push(y);
+                }
+            }
+        }
+        row
+    })
+                 .collect();
+    print_rows(&headings, &rows);
+
+    // Averages over the queries that succeeded; with --compare, over the
+    // queries that succeeded against both collections.
+    let succeeded: Vec<usize> = (0..judgments.len())
+        .filter(|&i| {
+            base[i].is_some() &&
+                other.as_ref().map_or(true, |other| other[i].is_some())
+        })
+        .collect();
+    let failed = judgments.len() - succeeded.len();
+    println!();
+    let a = mean(&scored(&base, &succeeded));
+    let mut headings = vec![
+        "mean over queries".to_string(),
+        collection_name(&collection),
+    ];
+    let rows: Vec<Vec<String>> = match other {
+        None => {
+            names.iter()
+                 .zip(a.iter())
+                 .map(|(name, x)| vec![name.clone(), fixed(*x)])
+                 .collect()
+        }
+        Some(ref other) => {
+            headings.push(collection_name(compared.as_ref().unwrap()));
+            headings.push("change".to_string());
+            let b = mean(&scored(other, &succeeded));
+            names.iter()
+                 .zip(a.iter().zip(b.iter()))
+                 .map(|(name, (x, y))| {
+                vec![name.clone(), fixed(*x), fixed(*y), signed(y - x)]
+            })
+                 .collect()
+        }
+    };
+    print_rows(&headings, &rows);
+    if failed > 0 {
+        println!(
+            "\n{} of {} queries failed and are left out of the means",
+            failed,
+            judgments.len()
+        );
+        std::process::exit(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Judgment, Scores, mean, metrics, score};
+    use std::collections::BTreeMap;
+
+    fn judgment(grades: &[(&str, f64)]) -> Judgment {
+        Judgment {
+            label: "test".to_string(),
+            query: Some("test".to_string()),
+            natural_language_query: None,
+            filter: None,
+            grades: grades.iter()
+                          .map(|&(id, grade)| (id.to_string(), grade))
+                          .collect::<BTreeMap<String, f64>>(),
+        }
+    }
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    fn assert_metrics(scores: [f64; 4], expected: [f64; 4]) {
+        for (actual, expected) in scores.iter().zip(expected.iter()) {
+            assert!(
+                (actual - expected).abs() < 1e-9,
+                "expected {:?}, got {:?}",
+                expected,
+                scores
+            );
+        }
+    }
+
+    #[test]
+    fn nothing_returned_scores_zero() {
+        let scores = score(&judgment(&[("a", 1.0)]), &[], 10);
+        assert_metrics(metrics(&scores), [0.0, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn no_relevant_documents_scores_zero() {
+        let scores = score(&judgment(&[("a", 0.0)]), &ids(&["a", "b"]), 2);
+        assert_metrics(metrics(&scores), [0.0, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn graded_judgments() {
+        // dcg = (2^1 - 1) / log2(2) + 0 + (2^3 - 1) / log2(4) = 4.5
+        // idcg = (2^3 - 1) / log2(2) + (2^1 - 1) / log2(3) + 0
+        let judged = judgment(&[("a", 3.0), ("b", 1.0), ("c", 0.0)]);
+        let scores = score(&judged, &ids(&["b", "x", "a"]), 3);
+        let idcg = 7.0 + 1.0 / 3f64.log2();
+        assert_metrics(metrics(&scores), [2.0 / 3.0, 1.0, 1.0, 4.5 / idcg]);
+    }
+
+    #[test]
+    fn k_beyond_the_results() {
+        // Precision is out of k, not out of what came back.
+        // dcg = 1 / log2(3), idcg = 1 / log2(2) + 1 / log2(3)
+        let judged = judgment(&[("a", 1.0), ("b", 1.0)]);
+        let scores = score(&judged, &ids(&["x", "a"]), 5);
+        let dcg = 1.0 / 3f64.log2();
+        assert_metrics(metrics(&scores), [0.2, 0.5, 0.5, dcg / (1.0 + dcg)]);
+    }
+
+    #[test]
+    fn mean_of_scores() {
+        let first = Scores {
+            precision: 1.0,
+            recall: 0.5,
+            reciprocal_rank: 1.0,
+            ndcg: 0.25,
+        };
+        let second = Scores {
+            precision: 0.0,
+            recall: 1.0,
+            reciprocal_rank: 0.5,
+            ndcg: 0.75,
+        };
+        assert_metrics(mean(&[&first, &second]), [0.5, 0.75, 0.75, 0.5]);
+        assert_metrics(mean(&[]), [0.0, 0.0, 0.0, 0.0]);
+    }
+}
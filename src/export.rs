@@ -0,0 +1,283 @@
+use apply::DEFAULT_CONFIGURATION;
+use backup::write_json;
+use clap;
+use diff::without_server_fields;
+use filter::{compiled_filter, filter_value};
+use info::discovery_service_info;
+use query::{RESULT_WINDOW, each_result, output};
+use render::{csv_cell, csv_row};
+use select::{configuration_with_id, read_only_environment, select_collection,
+             writable_environment};
+use serde_json::{Map, Value, to_writer};
+use std;
+use std::collections::BTreeSet;
+use std::fs::create_dir_all;
+use std::io::Write;
+use std::path::Path;
+use template::get_path;
+use wdsapi::common::{Credentials, QueryParams};
+use wdsapi::configuration;
+
+fn json_object() -> Value {
+    Value::Object(Map::new())
+}
+
+fn by_name(a: &Value, b: &Value) -> std::cmp::Ordering {
+    a["name"].as_str().cmp(&b["name"].as_str())
+}
+
+// A file name for a configuration, made from its name; unique among
+// those already used.
+fn configuration_file(name: &str, used: &mut BTreeSet<String>) -> String {
+    let stem: String = name.chars()
+                           .map(|c| if c.is_alphanumeric() { c } else { '-' })
+                           .collect();
+    let mut file = format!("configurations/{}.json", stem);
+    let mut n = 2;
+    while used.contains(&file) {
+        file = format!("configurations/{}-{}.json", stem, n);
+        n += 1;
+    }
+    used.insert(file.clone());
+    file
+}
+
+// Write manifest.json, in the form apply reads, and one file for each
+// configuration. Only fields apply uses are kept and everything is in
+// name order, so exports of an unchanged environment are identical.
+pub fn export_environment(creds: Credentials, matches: &clap::ArgMatches) {
+    let info = discovery_service_info(creds);
+    let env_info = writable_environment(&info);
+    let dir = Path::new(matches.value_of("directory").unwrap());
+    create_dir_all(dir.join("configurations")).expect(&format!(
+        "Failed to create {}",
+        dir.display()
+    ));
+
+    let mut configurations = env_info.configurations.clone();
+    configurations.sort_by(by_name);
+    let mut used = BTreeSet::new();
+    let mut configuration_entries = Vec::new();
+    for existing in &configurations {
+        let name = existing["name"].as_str().unwrap_or("");
+        if name == DEFAULT_CONFIGURATION {
+            continue;
+        }
+        let configuration_id = existing["configuration_id"].as_str().expect(
+            "Internal error: missing configuration_id",
+        );
+        let detail = match configuration::detail(
+            &info.creds,
+            &env_info.environment_id,
+            configuration_id,
+        ) {
+            Ok(response) => without_server_fields(&response),
+            Err(e) => {
+                println!("Failed to lookup configuration {}", e);
+                std::process::exit(1)
+            }
+        };
+        let file = configuration_file(name, &mut used);
+        write_json(&dir.join(&file), &detail);
+        let mut entry = json_object();
+        entry["name"] = Value::String(name.to_string());
+        entry["file"] = Value::String(file);
+        configuration_entries.push(entry);
+    }
+
+    let mut collections = env_info.collections.clone();
+    collections.sort_by(by_name);
+    let collection_entries: Vec<Value> = collections
+        .iter()
+        .map(|existing| {
+            let mut entry = json_object();
+            entry["name"] = existing["name"].clone();
+            if !existing["description"].is_null() {
+                entry["description"] = existing["description"].clone();
+            }
+            let used_configuration = configuration_with_id(
+                &env_info,
+                existing["configuration_id"].as_str().unwrap_or(""),
+            );
+            if !used_configuration["name"].is_null() {
+                entry["configuration"] = used_configuration["name"].clone();
+            }
+            entry
+        })
+        .collect();
+
+    let mut environment = json_object();
+    environment["name"] = env_info.environment["name"].clone();
+    if !env_info.environment["description"].is_null() {
+        environment["description"] =
+            env_info.environment["description"].clone();
+    }
+    environment["size"] = env_info.environment["size"].clone();
+
+    let mut manifest = json_object();
+    manifest["environment"] = environment;
+    manifest["configurations"] = Value::Array(configuration_entries);
+    manifest["collections"] = Value::Array(collection_entries);
+    write_json(&dir.join("manifest.json"), &manifest);
+    println!(
+        "Exported environment {} with {} configurations and {} collections \
+         to {}",
+        env_info.environment["name"],
+        manifest["configurations"].as_array().map_or(0, |c| c.len()),
+        manifest["collections"].as_array().map_or(0, |c| c.len()),
+        dir.display()
+    );
+}
+
+fn write_document(
+    out: &mut dyn Write,
+    document: &Value,
+    csv_fields: Option<&Vec<&str>>,
+) {
+    match csv_fields {
+        Some(fields) => {
+            let cells: Vec<String> = fields
+                .iter()
+                .map(|f| csv_cell(&get_path(document, f)))
+                .collect();
+            writeln!(out, "{}", csv_row(&cells)).expect("Failed to write row")
+        }
+        None => {
+            to_writer(&mut *out, document).expect("Failed to write document");
+            writeln!(out).expect("Failed to write document")
+        }
+    }
+}
+
+// Each query can only reach the first RESULT_WINDOW results. With
+// --partition-field the export runs in windows sorted on that field, each
+// starting at the last value the window before it reached; documents with
+// that boundary value which were already written are skipped.
+pub fn export_collection(creds: Credentials, matches: &clap::ArgMatches) {
+    let info = discovery_service_info(creds);
+    let env_info = if matches.is_present("read-only") {
+        read_only_environment(&info)
+    } else {
+        writable_environment(&info)
+    };
+    let collection = select_collection(&env_info, matches);
+    let col_id = collection["collection_id"].as_str().unwrap();
+    let env_id = &env_info.environment_id;
+    let fields: Option<Vec<&str>> =
+        matches.values_of("fields").map(|f| f.collect());
+    let csv_fields = if matches.value_of("format") == Some("csv") {
+        match fields {
+            Some(ref fields) => Some(fields),
+            None => {
+                println!("--format csv needs --fields");
+                std::process::exit(1)
+            }
+        }
+    } else {
+        None
+    };
+    let partition = matches.value_of("partition-field");
+    let user_filter = compiled_filter(matches);
+    // Ask only for the fields being written, plus what paging needs.
+    let return_hierarchy = fields.as_ref().map(|fields| {
+        let mut wanted = fields.clone();
+        wanted.push("id");
+        wanted.extend(partition);
+        wanted.join(",")
+    });
+
+    let mut out = output(matches);
+    if let Some(fields) = csv_fields {
+        let header: Vec<String> = fields.iter()
+                                        .map(|f| csv_cell(&Value::from(*f)))
+                                        .collect();
+        writeln!(out, "{}", csv_row(&header)).expect("Failed to write header");
+    }
+    let mut written: u64 = 0;
+    let mut boundary: Option<Value> = None;
+    let mut at_boundary: BTreeSet<String> = BTreeSet::new();
+    loop {
+        let filter = match (user_filter.as_ref(), partition, &boundary) {
+            (Some(f), Some(p), &Some(ref b)) => {
+                Some(format!("({}),{}>={}", f, p, filter_value(b)))
+            }
+            (None, Some(p), &Some(ref b)) => {
+                Some(format!("{}>={}", p, filter_value(b)))
+            }
+            (f, _, _) => f.cloned(),
+        };
+        let mut in_window: u64 = 0;
+        let mut last: Option<Value> = None;
+        let mut last_ids: BTreeSet<String> = BTreeSet::new();
+        let result = each_result(
+            &info.creds,
+            env_id,
+            col_id,
+            |offset, count| {
+                QueryParams {
+                    filter: filter.clone(),
+                    query: None,
+                    natural_language_query: None,
+                    passages: Some(false),
+                    aggregation: None,
+                    count: count,
+                    return_hierarchy: return_hierarchy.clone(),
+                    offset: Some(offset),
+                    sort: partition.map(|p| p.to_string()),
+                }
+            },
+            |document| {
+                in_window += 1;
+                let id = document["id"].as_str().unwrap_or("").to_string();
+                if at_boundary.contains(&id) {
+                    return;
+                }
+                write_document(&mut out, document, csv_fields);
+                written += 1;
+                if let Some(field) = partition {
+                    let value = get_path(document, field);
+                    if last.as_ref() != Some(&value) {
+                        last = Some(value);
+                        last_ids.clear();
+                    }
+                    last_ids.insert(id);
+                }
+            },
+        );
+        let matching_results = match result {
+            Ok(matching_results) => matching_results,
+            Err(e) => {
+                out.flush().expect("Failed to write export");
+                eprintln!("Failed after {} documents {}", written, e);
+                std::process::exit(1)
+            }
+        };
+        if partition.is_none() || in_window < RESULT_WINDOW {
+            out.flush().expect("Failed to write export");
+            eprintln!("Exported {} documents", written);
+            if partition.is_none() && matching_results > written {
+                eprintln!(
+                    "{} documents matched, but one query reaches only the \
+                     first {}; use --partition-field to export the rest",
+                    matching_results,
+                    RESULT_WINDOW
+                )
+            }
+            return;
+        }
+        if last.is_none() || last == boundary ||
+            last == Some(Value::Null)
+        {
+            out.flush().expect("Failed to write export");
+            eprintln!(
+                "More than {} documents share a value of {}; choose a \
+                 partition field with more distinct values",
+                RESULT_WINDOW,
+                partition.unwrap()
+            );
+            std::process::exit(1)
+        }
+        boundary = last;
+        at_boundary = last_ids;
+    }
+}
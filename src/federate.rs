@@ -0,0 +1,226 @@
+use clap;
+use info::EnvironmentInfo;
+use rayon::prelude::*;
+use select::{collection_with_id, collection_with_name, newest_collection,
+             oldest_collection};
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+use wdsapi::common::{Credentials, QueryParams};
+use wdsapi::query;
+
+// Every collection chosen by the selectors given to query, without
+// repeats and in the order given.
+pub fn selected_collections(
+    env_info: &EnvironmentInfo,
+    matches: &clap::ArgMatches,
+) -> Vec<Value> {
+    let mut selected: Vec<Value> = Vec::new();
+    if matches.is_present("all-collections") {
+        selected.extend(env_info.collections.iter().cloned());
+    }
+    if matches.is_present("newest") {
+        selected.push(newest_collection(env_info));
+    }
+    if matches.is_present("oldest") {
+        selected.push(oldest_collection(env_info));
+    }
+    if let Some(names) = matches.values_of("named") {
+        selected.extend(names.map(|name| collection_with_name(env_info, name)));
+    }
+    if let Some(ids) = matches.values_of("id") {
+        selected.extend(ids.map(|id| collection_with_id(env_info, id)));
+    }
+    let mut unique: Vec<Value> = Vec::new();
+    for collection in selected {
+        let id = collection["collection_id"].clone();
+        if !unique.iter().any(|c| c["collection_id"] == id) {
+            unique.push(collection)
+        }
+    }
+    unique
+}
+
+fn score_of(result: &Value) -> f64 {
+    result["score"]
+        .as_f64()
+        .or_else(|| result["result_metadata"]["score"].as_f64())
+        .unwrap_or(0.0)
+}
+
+fn count_of(value: &Value) -> u64 {
+    value["matching_results"].as_u64().unwrap_or(0)
+}
+
+// Two aggregations can be combined when they asked the same question.
+fn same_request(a: &Value, b: &Value) -> bool {
+    ["type", "field", "path", "match", "interval"]
+        .iter()
+        .all(|key| a[*key] == b[*key])
+}
+
+fn aggregations_of(value: &Value) -> Vec<Value> {
+    match value["aggregations"].as_array() {
+        Some(aggregations) => aggregations.clone(),
+        None => Vec::new(),
+    }
+}
+
+// Add buckets with the same key together, combining their own
+// aggregations the same way.
+fn combine_buckets(a: &[Value], b: &[Value]) -> Vec<Value> {
+    let mut combined: Vec<Value> = a.to_vec();
+    for bucket in b {
+        match combined.iter().position(|c| c["key"] == bucket["key"]) {
+            Some(i) => {
+                let sum = count_of(&combined[i]) + count_of(bucket);
+                let nested = combine_lists(
+                    &aggregations_of(&combined[i]),
+                    &aggregations_of(bucket),
+                );
+                combined[i]["matching_results"] = Value::from(sum);
+                if !nested.is_empty() {
+                    combined[i]["aggregations"] = Value::Array(nested);
+                }
+            }
+            None => combined.push(bucket.clone()),
+        }
+    }
+    combined
+}
+
+// The combination of two aggregations of the same request, or None when
+// their values cannot be combined, like an average.
+fn combine(a: &Value, b: &Value) -> Option<Value> {
+    let mut combined = a.clone();
+    match a["type"].as_str().unwrap_or("") {
+        "term" | "histogram" | "timeslice" => {
+            let empty = Vec::new();
+            let mut buckets = combine_buckets(
+                a["results"].as_array().unwrap_or(&empty),
+                b["results"].as_array().unwrap_or(&empty),
+            );
+            if a["type"] == "term" {
+                buckets.sort_by(|x, y| count_of(y).cmp(&count_of(x)));
+                if let Some(count) = a["count"].as_u64() {
+                    buckets.truncate(count as usize);
+                }
+            } else {
+                buckets.sort_by(|x, y| {
+                    x["key"].as_f64()
+                            .partial_cmp(&y["key"].as_f64())
+                            .unwrap_or(Ordering::Equal)
+                });
+            }
+            combined["results"] = Value::Array(buckets);
+        }
+        "filter" | "nested" => {
+            combined["matching_results"] =
+                Value::from(count_of(a) + count_of(b));
+            combined["aggregations"] = Value::Array(combine_lists(
+                &aggregations_of(a),
+                &aggregations_of(b),
+            ));
+        }
+        "sum" | "max" | "min" => {
+            let (x, y) = (a["value"].as_f64()?, b["value"].as_f64()?);
+            combined["value"] = Value::from(match a["type"].as_str() {
+                Some("sum") => x + y,
+                Some("max") => x.max(y),
+                _ => x.min(y),
+            });
+        }
+        _ => return None,
+    }
+    Some(combined)
+}
+
+// Combine aggregation lists position by position; anything that cannot be
+// combined is left out.
+fn combine_lists(a: &[Value], b: &[Value]) -> Vec<Value> {
+    a.iter()
+     .zip(b)
+     .filter(|&(x, y)| same_request(x, y))
+     .filter_map(|(x, y)| combine(x, y))
+     .collect()
+}
+
+// Query each collection in parallel and merge the responses into one: the
+// best scoring results from all of them, each with a "collection" naming
+// where it came from, and the aggregations added together. Collections
+// whose query failed are reported and listed in "failed_collections".
+pub fn federated_query<P>(
+    creds: &Credentials,
+    env_id: &str,
+    collections: &[Value],
+    count: u64,
+    params: P,
+) -> Value
+where
+    P: Fn() -> QueryParams + Sync,
+{
+    let outcomes: Vec<(String, Option<Value>)> = collections
+        .par_iter()
+        .map(|collection| {
+            let name = collection["name"].as_str().unwrap_or("").to_string();
+            match query::query(
+                creds,
+                env_id,
+                collection["collection_id"].as_str().unwrap_or(""),
+                params(),
+            ) {
+                Ok(response) => (name, Some(response)),
+                Err(e) => {
+                    eprintln!("Query of collection {} failed {}", name, e);
+                    (name, None)
+                }
+            }
+        })
+        .collect();
+    let mut responses: Vec<(String, Value)> = Vec::new();
+    let mut failed: Vec<Value> = Vec::new();
+    for (name, outcome) in outcomes {
+        match outcome {
+            Some(response) => responses.push((name, response)),
+            None => failed.push(Value::String(name)),
+        }
+    }
+
+    let mut results: Vec<Value> = Vec::new();
+    let mut matching_results = 0;
+    let mut aggregations: Option<Vec<Value>> = None;
+    for &(ref name, ref response) in &responses {
+        matching_results += count_of(response);
+        if let Some(found) = response["results"].as_array() {
+            for result in found {
+                let mut result = result.clone();
+                result["collection"] = Value::String(name.clone());
+                results.push(result);
+            }
+        }
+        let these = aggregations_of(response);
+        aggregations = Some(match aggregations {
+            None => these,
+            Some(so_far) => combine_lists(&so_far, &these),
+        });
+    }
+    results.sort_by(|a, b| {
+        score_of(b).partial_cmp(&score_of(a)).unwrap_or(Ordering::Equal)
+    });
+    results.truncate(count as usize);
+
+    let mut merged = Value::Object(Map::new());
+    merged["matching_results"] = Value::from(matching_results);
+    merged["collections"] = Value::Array(
+        responses.iter().map(|r| Value::String(r.0.clone())).collect(),
+    );
+    merged["results"] = Value::Array(results);
+    if !failed.is_empty() {
+        merged["failed_collections"] = Value::Array(failed);
+    }
+    if let Some(aggregations) = aggregations {
+        if !aggregations.is_empty() {
+            merged["aggregations"] = Value::Array(aggregations);
+        }
+    }
+    merged
+}
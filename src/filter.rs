@@ -0,0 +1,192 @@
+use clap;
+use serde_json::Value;
+use std;
+
+// A value as it is written on the right of a filter operator: numbers
+// as they are, anything else quoted with \ and " escaped.
+pub fn filter_value(value: &Value) -> String {
+    match *value {
+        Value::String(ref s) => quote(s),
+        ref other => other.to_string(),
+    }
+}
+
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// A plain decimal like 12, -3 or 0.5. Rust would also parse inf, NaN and
+// 1e5 as numbers, but those mean something else to the service.
+fn is_decimal(text: &str) -> bool {
+    let digits = text.trim_start_matches('-');
+    let mut parts = digits.splitn(2, '.');
+    let all_digits = |part: &str| {
+        !part.is_empty() && part.chars().all(|c| c.is_ascii_digit())
+    };
+    text.len() - digits.len() <= 1 && all_digits(parts.next().unwrap()) &&
+        parts.next().map_or(true, all_digits)
+}
+
+fn operand(text: &str) -> String {
+    if is_decimal(text) {
+        text.to_string()
+    } else {
+        quote(text)
+    }
+}
+
+fn is_field_name(field: &str) -> bool {
+    !field.is_empty() &&
+        field.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+}
+
+// Field names go into the filter unquoted, so only allow what a field
+// name can contain.
+fn checked_field(field: &str, flag: &str) -> String {
+    if !is_field_name(field) {
+        println!("Expected a field name for {}, not {:?}", flag, field);
+        std::process::exit(1)
+    }
+    field.to_string()
+}
+
+fn field_and_value(text: &str, flag: &str) -> (String, String) {
+    let mut parts = text.splitn(2, '=');
+    let field = parts.next().unwrap();
+    match parts.next() {
+        Some(value) => (checked_field(field, flag), operand(value)),
+        None => {
+            println!("Expected field=value for {}, not {}", flag, text);
+            std::process::exit(1)
+        }
+    }
+}
+
+// Each --where flag, with the filter operator it compiles to.
+const COMPARISONS: &[(&str, &str)] = &[
+    ("where", "::"),
+    ("where-contains", ":"),
+    ("where-gt", ">"),
+    ("where-lt", "<"),
+];
+
+// The --filter, if any, and every --where flag joined with "," (and).
+pub fn compiled_filter(matches: &clap::ArgMatches) -> Option<String> {
+    let mut terms = Vec::new();
+    if let Some(raw) = matches.value_of("filter") {
+        terms.push(raw.to_string());
+    }
+    for &(flag, operator) in COMPARISONS {
+        if let Some(values) = matches.values_of(flag) {
+            for text in values {
+                let (field, value) = field_and_value(text, flag);
+                terms.push(format!("{}{}{}", field, operator, value));
+            }
+        }
+    }
+    if let Some(fields) = matches.values_of("where-exists") {
+        for field in fields {
+            terms.push(format!("{}:*", checked_field(field, "where-exists")));
+        }
+    }
+    let filter = match terms.len() {
+        0 => None,
+        1 => terms.pop(),
+        // A raw filter may use | (or), so keep it together.
+        _ if matches.is_present("filter") => {
+            terms[0] = format!("({})", terms[0]);
+            Some(terms.join(","))
+        }
+        _ => Some(terms.join(",")),
+    };
+    if matches.is_present("show-filter") {
+        eprintln!("filter: {}", filter.as_ref().map_or("", |f| f.as_str()));
+    }
+    filter
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{checked_field, compiled_filter, is_field_name, operand, quote};
+    use cli::build_cli;
+
+    fn filter_for(args: &[&str]) -> Option<String> {
+        let mut argv = vec!["wdscli", "query"];
+        argv.extend(args);
+        let matches = build_cli().get_matches_from(argv);
+        compiled_filter(matches.subcommand_matches("query").unwrap())
+    }
+
+    #[test]
+    fn quote_escapes_quotes_and_backslashes() {
+        assert_eq!(quote("plain"), "\"plain\"");
+        assert_eq!(quote("say \"hi\""), "\"say \\\"hi\\\"\"");
+        assert_eq!(quote("a\\b"), "\"a\\\\b\"");
+        assert_eq!(quote("a\\\"b"), "\"a\\\\\\\"b\"");
+    }
+
+    #[test]
+    fn operand_leaves_only_decimals_unquoted() {
+        assert_eq!(operand("42"), "42");
+        assert_eq!(operand("-3"), "-3");
+        assert_eq!(operand("0.5"), "0.5");
+        assert_eq!(operand("inf"), "\"inf\"");
+        assert_eq!(operand("infinity"), "\"infinity\"");
+        assert_eq!(operand("NaN"), "\"NaN\"");
+        assert_eq!(operand("1e5"), "\"1e5\"");
+        assert_eq!(operand("+1"), "\"+1\"");
+        assert_eq!(operand("--1"), "\"--1\"");
+        assert_eq!(operand("1."), "\"1.\"");
+        assert_eq!(operand(".5"), "\".5\"");
+        assert_eq!(operand("1.2.3"), "\"1.2.3\"");
+        assert_eq!(operand(""), "\"\"");
+    }
+
+    #[test]
+    fn operand_quotes_filter_syntax_in_values() {
+        assert_eq!(operand("a,b"), "\"a,b\"");
+        assert_eq!(operand("a|b"), "\"a|b\"");
+        assert_eq!(operand("1,000"), "\"1,000\"");
+        assert_eq!(operand("say \"hi\""), "\"say \\\"hi\\\"\"");
+    }
+
+    #[test]
+    fn checked_field_allows_dotted_names() {
+        assert_eq!(
+            checked_field("extracted_metadata.title", "where"),
+            "extracted_metadata.title"
+        );
+        assert!(is_field_name("enriched_text.entities.text"));
+        assert!(!is_field_name(""));
+        assert!(!is_field_name("title,id"));
+        assert!(!is_field_name("title|id"));
+        assert!(!is_field_name("title:x"));
+        assert!(!is_field_name("title\""));
+        assert!(!is_field_name("title id"));
+    }
+
+    #[test]
+    fn compiled_filter_joins_terms() {
+        assert_eq!(filter_for(&[]), None);
+        assert_eq!(
+            filter_for(&["--where", "title=a,b"]),
+            Some("title::\"a,b\"".to_string())
+        );
+        assert_eq!(
+            filter_for(&["--where-gt", "year=2000", "--where-exists", "id"]),
+            Some("year>2000,id:*".to_string())
+        );
+    }
+
+    #[test]
+    fn compiled_filter_keeps_a_raw_filter_together() {
+        assert_eq!(
+            filter_for(&["--filter", "a:1|b:2"]),
+            Some("a:1|b:2".to_string())
+        );
+        assert_eq!(
+            filter_for(&["--filter", "a:1|b:2", "--where", "c=x|y"]),
+            Some("(a:1|b:2),c::\"x|y\"".to_string())
+        );
+    }
+}
@@ -1,32 +1,65 @@
 #[macro_use]
 extern crate clap;
 extern crate crossbeam;
+extern crate glob;
 extern crate hyper;
+extern crate hyper_rustls;
 extern crate rayon;
+extern crate regex;
+extern crate rustyline;
 extern crate serde_json;
+extern crate serde_yaml;
+extern crate toml;
 extern crate walkdir;
 extern crate wdsapi;
 
 mod add;
+mod apply;
+mod backup;
+mod bulk;
 mod cli;
+mod clone;
+mod confirm;
 mod create;
 mod delete;
+mod diff;
+mod evaluate;
+mod export;
+mod federate;
+mod filter;
 mod info;
 mod query;
+mod render;
+mod request;
 mod select;
+mod shell;
 mod show;
+mod size;
+mod template;
+mod update;
+mod validate;
+mod wait;
 
 use add::add_document;
+use apply::{apply, plan};
+use clone::clone_collection;
 use create::{create_collection, create_configuration, create_environment};
 use delete::{delete_collection, delete_configuration, delete_document,
-             delete_environment};
+             delete_environment, purge_collection};
+use diff::diff_configuration;
+use evaluate::evaluate;
+use export::{export_collection, export_environment};
 use info::{EnvironmentInfo, discovery_service_info};
 use query::{notices, query};
 use select::{configuration_with_id, select_collection, writable_environment};
 use serde_json::Value;
+use shell::shell;
 use show::{show_collection, show_configuration, show_document,
            show_environment, show_preview};
 use std::io::stdout;
+use size::list_environment_sizes;
+use update::{resize_environment, update_collection, update_configuration};
+use validate::validate_configuration;
 
 use wdsapi::common::{ApiError, Credentials, credentials_from_file};
 
@@ -224,6 +257,7 @@ fn main() {
     // Just a few commands do not need credentials.
     match matches.subcommand() {
         ("generate-completions", Some(m)) => generate_completions(m),
+        ("validate-configuration", Some(m)) => validate_configuration(m),
         _ => subcommand_needing_credentials(&matches),
     }
 }
@@ -242,6 +276,9 @@ fn subcommand_needing_credentials(matches: &clap::ArgMatches) {
         Ok(creds) => {
             match matches.subcommand() {
                 ("overview", Some(m)) => show(creds, m),
+                ("plan", Some(m)) => plan(creds, m),
+                ("evaluate", Some(m)) => evaluate(creds, m),
+                ("apply", Some(m)) => apply(creds, m),
                 ("query", Some(m)) => query(creds, m),
                 ("notices", Some(m)) => notices(creds, m),
                 ("preview", Some(m)) => show_preview(creds, m),
@@ -249,6 +286,7 @@ fn subcommand_needing_credentials(matches: &clap::ArgMatches) {
                     create_environment(&creds, m)
                 }
                 ("create-collection", Some(m)) => create_collection(creds, m),
+                ("clone-collection", Some(m)) => clone_collection(creds, m),
                 ("create-configuration", Some(m)) => {
                     create_configuration(creds, m)
                 }
@@ -258,6 +296,23 @@ fn subcommand_needing_credentials(matches: &clap::ArgMatches) {
                 ("delete-configuration", Some(m)) => {
                     delete_configuration(creds, m)
                 }
+                ("purge-collection", Some(m)) => purge_collection(creds, m),
+                ("list-environment-sizes", Some(_)) => {
+                    list_environment_sizes(creds)
+                }
+                ("resize-environment", Some(m)) => resize_environment(creds, m),
+                ("update-collection", Some(m)) => update_collection(creds, m),
+                ("update-configuration", Some(m)) => {
+                    update_configuration(creds, m)
+                }
+                ("diff-configuration", Some(m)) => {
+                    diff_configuration(creds, m)
+                }
+                ("export-collection", Some(m)) => export_collection(creds, m),
+                ("export-environment", Some(m)) => {
+                    export_environment(creds, m)
+                }
+                ("shell", Some(m)) => shell(creds, m),
                 ("show-environment", Some(m)) => show_environment(creds, m),
                 ("show-collection", Some(m)) => show_collection(creds, m),
                 ("show-configuration", Some(m)) => show_configuration(creds, m),
@@ -1,16 +1,118 @@
 use clap;
-use info::discovery_service_info;
+use filter::compiled_filter;
+use federate::{federated_query, selected_collections};
+use info::{EnvironmentInfo, discovery_service_info};
+use render::{print_aggregations, print_aggregations_csv, print_table};
 use select::{read_only_environment, select_collection, writable_environment};
+use serde_json::{Value, to_writer};
 use serde_json::ser::to_string_pretty;
-use wdsapi::common::{Credentials, QueryParams};
+use std;
+use std::cmp::min;
+use std::fs::File;
+use std::io::{BufWriter, IsTerminal, Write, stdout};
+use wdsapi::common::{ApiError, Credentials, QueryParams};
 use wdsapi::query;
 
+// The service rejects queries where offset plus count is beyond this.
+pub const RESULT_WINDOW: u64 = 10000;
+const PAGE_SIZE: u64 = 100;
+
+// Page through the results of `request`, calling `f` with each result,
+// until the matching results run out or the result window is reached.
+// `params` builds the query for a page from its offset and count. Returns
+// the matching_results reported by the service.
+fn each_page_result<R, P, F>(
+    request: R,
+    params: P,
+    mut f: F,
+) -> Result<u64, ApiError>
+where
+    R: Fn(QueryParams) -> Result<Value, ApiError>,
+    P: Fn(u64, u64) -> QueryParams,
+    F: FnMut(&Value),
+{
+    let mut offset = 0;
+    loop {
+        let count = min(PAGE_SIZE, RESULT_WINDOW - offset);
+        let response = request(params(offset, count))?;
+        let matching_results =
+            response["matching_results"].as_u64().unwrap_or(0);
+        let results = match response["results"].as_array() {
+            Some(results) => results.clone(),
+            None => Vec::new(),
+        };
+        for result in &results {
+            f(result)
+        }
+        offset += results.len() as u64;
+        if results.is_empty() || offset >= matching_results ||
+            offset >= RESULT_WINDOW
+        {
+            return Ok(matching_results);
+        }
+    }
+}
+
+// Run a query a page at a time, calling `f` with each result; see
+// each_page_result.
+pub fn each_result<P, F>(
+    creds: &Credentials,
+    env_id: &str,
+    col_id: &str,
+    params: P,
+    f: F,
+) -> Result<u64, ApiError>
+where
+    P: Fn(u64, u64) -> QueryParams,
+    F: FnMut(&Value),
+{
+    each_page_result(
+        |page| query::query(creds, env_id, col_id, page),
+        params,
+        f,
+    )
+}
+
+// The same for the collection's notices.
+pub fn each_notice<P, F>(
+    creds: &Credentials,
+    env_id: &str,
+    col_id: &str,
+    params: P,
+    f: F,
+) -> Result<u64, ApiError>
+where
+    P: Fn(u64, u64) -> QueryParams,
+    F: FnMut(&Value),
+{
+    each_page_result(
+        |page| query::notices(creds, env_id, col_id, page),
+        params,
+        f,
+    )
+}
+
+// Parameters to page through every document in a collection.
+pub fn all_documents(offset: u64, count: u64) -> QueryParams {
+    QueryParams {
+        filter: None,
+        query: None,
+        natural_language_query: None,
+        passages: Some(false),
+        aggregation: None,
+        count: count,
+        return_hierarchy: None,
+        offset: Some(offset),
+        sort: None,
+    }
+}
+
 fn query_params(
     matches: &clap::ArgMatches,
     default_count: &str,
 ) -> QueryParams {
     QueryParams {
-        filter: matches.value_of("filter").map(|s| s.to_string()),
+        filter: compiled_filter(matches),
         query: matches.value_of("query").map(|s| s.to_string()),
         natural_language_query:
             matches.value_of("natural_language_query").map(
@@ -22,9 +124,12 @@ fn query_params(
                       .unwrap_or(default_count)
                       .parse::<u64>()
                       .unwrap(),
-        return_hierarchy: matches.value_of("return_hierarchy").map(
-            |s| s.to_string(),
-        ),
+        return_hierarchy: match matches.values_of("fields") {
+            Some(fields) => Some(fields.collect::<Vec<&str>>().join(",")),
+            None => {
+                matches.value_of("return_hierarchy").map(|s| s.to_string())
+            }
+        },
         offset: matches.value_of("offset").map(
             |s| s.parse::<u64>().unwrap(),
         ),
@@ -32,6 +137,168 @@ fn query_params(
     }
 }
 
+// The file named by --out, or stdout.
+pub fn output(matches: &clap::ArgMatches) -> BufWriter<Box<dyn Write>> {
+    let out: Box<dyn Write> = match matches.value_of("out") {
+        Some(filename) => {
+            Box::new(File::create(filename).expect(
+                &format!("Failed to create {}", filename),
+            ))
+        }
+        None => Box::new(stdout()),
+    };
+    BufWriter::new(out)
+}
+
+// Write every result, one JSON object per line, to --out or stdout. The
+// summary goes to stderr so it never mixes with the results.
+fn query_all(
+    creds: &Credentials,
+    env_id: &str,
+    col_id: &str,
+    matches: &clap::ArgMatches,
+) {
+    let mut out = output(matches);
+    let mut written: u64 = 0;
+    let result = each_result(
+        creds,
+        env_id,
+        col_id,
+        |offset, count| {
+            QueryParams {
+                count: count,
+                offset: Some(offset),
+                ..query_params(matches, "1")
+            }
+        },
+        |result| {
+            to_writer(&mut out, result).expect("Failed to write result");
+            writeln!(out).expect("Failed to write result");
+            written += 1;
+        },
+    );
+    out.flush().expect("Failed to write results");
+    match result {
+        Ok(matching_results) => {
+            eprintln!(
+                "Wrote {} of {} matching results",
+                written,
+                matching_results
+            );
+            if matching_results > written && written >= RESULT_WINDOW {
+                eprintln!(
+                    "The service returns no more than the first {} results; \
+                     narrow the query with --filter to get the rest",
+                    RESULT_WINDOW
+                )
+            }
+        }
+        Err(e) => {
+            eprintln!("Failed after {} results {}", written, e);
+            std::process::exit(1)
+        }
+    }
+}
+
+// Default columns for table output when no fields are given.
+pub const TABLE_FIELDS: &[&str] = &["id", "score"];
+
+fn print_results_table(response: &Value, fields: &[&str]) {
+    let results = match response["results"].as_array() {
+        Some(results) => results.clone(),
+        None => Vec::new(),
+    };
+    print_table(&results, fields);
+    println!(
+        "{} of {} matching results",
+        results.len(),
+        response["matching_results"]
+    );
+}
+
+// Print a query response: results as JSON or a table of `fields`, and
+// any aggregations as text, CSV or within the JSON.
+pub fn print_response(
+    response: &Value,
+    table: bool,
+    fields: &[&str],
+    aggregation_output: &str,
+) {
+    let aggregations = match response["aggregations"].as_array() {
+        Some(aggregations) => aggregations.clone(),
+        None => Vec::new(),
+    };
+    if !aggregations.is_empty() && aggregation_output == "csv" {
+        print_aggregations_csv(&aggregations)
+    } else if !aggregations.is_empty() && aggregation_output == "text" {
+        if table {
+            print_results_table(response, fields);
+            println!();
+        } else {
+            println!("{} matching results\n", response["matching_results"]);
+        }
+        print_aggregations(&aggregations)
+    } else if table {
+        print_results_table(response, fields)
+    } else {
+        println!(
+            "{}",
+            to_string_pretty(response).expect(
+                "Internal error: failed to format query::query response",
+            )
+        )
+    }
+}
+
+fn aggregation_output<'a>(matches: &'a clap::ArgMatches) -> &'a str {
+    match matches.value_of("aggregation-output") {
+        Some(output) => output,
+        None if stdout().is_terminal() => "text",
+        None => "json",
+    }
+}
+
+// Default table columns when querying several collections.
+const FEDERATED_FIELDS: &[&str] = &["collection", "id", "score"];
+
+fn query_collections(
+    creds: &Credentials,
+    env_info: &EnvironmentInfo,
+    collections: &[Value],
+    matches: &clap::ArgMatches,
+) {
+    if matches.is_present("all") {
+        println!("--all works with one collection at a time");
+        std::process::exit(1)
+    }
+    // Each collection would skip its own first results, not the merged
+    // ones, so there is no right answer to page through.
+    if matches.is_present("offset") {
+        println!("--offset works with one collection at a time");
+        std::process::exit(1)
+    }
+    let fields: Vec<&str> = match matches.values_of("fields") {
+        Some(fields) => fields.collect(),
+        None => FEDERATED_FIELDS.to_vec(),
+    };
+    let response = federated_query(
+        creds,
+        &env_info.environment_id,
+        collections,
+        query_params(matches, "1").count,
+        || query_params(matches, "1"),
+    );
+    print_response(
+        &response,
+        matches.value_of("output") == Some("table"),
+        &fields,
+        aggregation_output(matches),
+    );
+    if !response["failed_collections"].is_null() {
+        std::process::exit(1)
+    }
+}
+
 pub fn query(creds: Credentials, matches: &clap::ArgMatches) {
     let info = discovery_service_info(creds);
     let env_info = if matches.is_present("read-only") {
@@ -39,9 +306,25 @@ pub fn query(creds: Credentials, matches: &clap::ArgMatches) {
     } else {
         writable_environment(&info)
     };
+    let collections = selected_collections(&env_info, matches);
+    if collections.len() > 1 {
+        return query_collections(&info.creds, &env_info, &collections, matches);
+    }
     let collection = select_collection(&env_info, matches);
     let env_id = env_info.environment_id;
+    if matches.is_present("all") {
+        return query_all(
+            &info.creds,
+            &env_id,
+            collection["collection_id"].as_str().unwrap(),
+            matches,
+        );
+    }
     let params = query_params(matches, "1");
+    let fields: Vec<&str> = match matches.values_of("fields") {
+        Some(fields) => fields.collect(),
+        None => TABLE_FIELDS.to_vec(),
+    };
 
     match query::query(
         &info.creds,
@@ -50,12 +333,11 @@ pub fn query(creds: Credentials, matches: &clap::ArgMatches) {
         params,
     ) {
         Ok(response) => {
-            println!(
-                "{}",
-                to_string_pretty(&response).expect(
-                    "Internal error: failed to format \
-                                  query::query response",
-                )
+            print_response(
+                &response,
+                matches.value_of("output") == Some("table"),
+                &fields,
+                aggregation_output(matches),
             )
         }
         Err(e) => println!("Failed to lookup collection {}", e),
@@ -0,0 +1,307 @@
+use serde_json::{Value, to_string};
+use template::get_path;
+
+// Longest a table cell may be before it is cut short.
+const MAX_CELL_WIDTH: usize = 40;
+
+// A value as plain text; strings without quotes, anything else as JSON.
+pub fn plain_text(value: &Value) -> String {
+    match *value {
+        Value::Null => String::new(),
+        Value::String(ref s) => s.clone(),
+        ref other => to_string(other).unwrap_or_default(),
+    }
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    let flat = text.replace(|c: char| c.is_whitespace(), " ");
+    if flat.chars().count() <= width {
+        flat
+    } else {
+        let kept: String = flat.chars().take(width - 1).collect();
+        format!("{}…", kept)
+    }
+}
+
+fn pad(text: &str, width: usize) -> String {
+    let len = text.chars().count();
+    format!("{}{}", text, " ".repeat(width - len))
+}
+
+// Print rows as columns lined up under their headings; long cells are cut
+// short.
+pub fn print_rows(headings: &[String], rows: &[Vec<String>]) {
+    let headings: Vec<String> =
+        headings.iter().map(|h| truncate(h, MAX_CELL_WIDTH)).collect();
+    let rows: Vec<Vec<String>> = rows.iter()
+                                     .map(|row| {
+        row.iter().map(|cell| truncate(cell, MAX_CELL_WIDTH)).collect()
+    })
+                                     .collect();
+    let widths: Vec<usize> = (0..headings.len())
+        .map(|i| {
+            rows.iter()
+                .map(|row| row.get(i).map_or(0, |c| c.chars().count()))
+                .chain(Some(headings[i].chars().count()))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let line = |cells: &[String]| {
+        let padded: Vec<String> = cells.iter()
+                                       .zip(&widths)
+                                       .map(|(cell, width)| pad(cell, *width))
+                                       .collect();
+        println!("{}", padded.join("  ").trim_end());
+    };
+    line(&headings);
+    let rules: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+    line(&rules);
+    for row in &rows {
+        line(row);
+    }
+}
+
+// A value as one CSV cell; nested values are written as JSON.
+pub fn csv_cell(value: &Value) -> String {
+    let text = plain_text(value);
+    if text.contains(|c| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text
+    }
+}
+
+pub fn csv_row(cells: &[String]) -> String {
+    cells.join(",")
+}
+
+// Print one row for each result with a column for each field path.
+pub fn print_table(results: &[Value], fields: &[&str]) {
+    let headings: Vec<String> = fields.iter().map(|f| f.to_string()).collect();
+    let rows: Vec<Vec<String>> = results.iter()
+                                        .map(|result| {
+        fields.iter()
+              .map(|f| plain_text(&get_path(result, f)))
+              .collect()
+    })
+                                        .collect();
+    print_rows(&headings, &rows);
+}
+
+// Widest bar drawn for a term or histogram bucket.
+const BAR_WIDTH: usize = 30;
+const SPARKS: &[char] = &['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+// How an aggregation was asked for, like term(enriched_text.entities.text).
+fn aggregation_label(aggregation: &Value) -> String {
+    let argument = ["field", "path", "match", "size"]
+        .iter()
+        .map(|name| &aggregation[*name])
+        .find(|value| !value.is_null())
+        .map(plain_text)
+        .unwrap_or_default();
+    format!(
+        "{}({})",
+        aggregation["type"].as_str().unwrap_or("?"),
+        argument
+    )
+}
+
+fn count_of(bucket: &Value) -> u64 {
+    bucket["matching_results"].as_u64().unwrap_or(0)
+}
+
+fn bucket_key(bucket: &Value) -> String {
+    if bucket["key_as_string"].is_string() {
+        plain_text(&bucket["key_as_string"])
+    } else {
+        plain_text(&bucket["key"])
+    }
+}
+
+fn bar(count: u64, max: u64) -> String {
+    let width = if max == 0 {
+        0
+    } else {
+        (count * BAR_WIDTH as u64 / max) as usize
+    };
+    "█".repeat(if count > 0 { width.max(1) } else { 0 })
+}
+
+fn sparkline(counts: &[u64]) -> String {
+    let max = counts.iter().cloned().max().unwrap_or(0);
+    counts.iter()
+          .map(|&count| if max == 0 {
+        SPARKS[0]
+    } else {
+        SPARKS[(count * (SPARKS.len() as u64 - 1) / max) as usize]
+    })
+          .collect()
+}
+
+fn children(value: &Value) -> Vec<Value> {
+    match value["aggregations"].as_array() {
+        Some(aggregations) => aggregations.clone(),
+        None => Vec::new(),
+    }
+}
+
+// Ranked rows with bars; each bucket's own aggregations are drawn
+// underneath it, indented.
+fn print_buckets(indent: &str, buckets: &[Value]) {
+    let keys: Vec<String> = buckets
+        .iter()
+        .map(|b| truncate(&bucket_key(b), MAX_CELL_WIDTH))
+        .collect();
+    let key_width = keys.iter().map(|k| k.chars().count()).max().unwrap_or(0);
+    let max = buckets.iter().map(count_of).max().unwrap_or(0);
+    let count_width = max.to_string().len();
+    for (i, (bucket, key)) in buckets.iter().zip(&keys).enumerate() {
+        println!(
+            "{}{:>3}. {}  {:>width$}  {}",
+            indent,
+            i + 1,
+            pad(key, key_width),
+            count_of(bucket),
+            bar(count_of(bucket), max),
+            width = count_width
+        );
+        print_aggregation_list(&format!("{}     ", indent), &children(bucket));
+    }
+}
+
+fn print_aggregation(indent: &str, aggregation: &Value) {
+    let label = aggregation_label(aggregation);
+    let buckets = match aggregation["results"].as_array() {
+        Some(results) => results.clone(),
+        None => Vec::new(),
+    };
+    match aggregation["type"].as_str().unwrap_or("") {
+        "term" | "histogram" => {
+            println!("{}{}", indent, label);
+            print_buckets(indent, &buckets);
+        }
+        "timeslice" => {
+            let counts: Vec<u64> = buckets.iter().map(count_of).collect();
+            println!(
+                "{}{} every {}: {} {} {} (max {})",
+                indent,
+                label,
+                plain_text(&aggregation["interval"]),
+                buckets.first().map(bucket_key).unwrap_or_default(),
+                sparkline(&counts),
+                buckets.last().map(bucket_key).unwrap_or_default(),
+                counts.iter().max().unwrap_or(&0)
+            );
+            if buckets.iter().any(|b| !children(b).is_empty()) {
+                print_buckets(indent, &buckets);
+            }
+        }
+        "filter" | "nested" => {
+            println!(
+                "{}{}: {} matching results",
+                indent,
+                label,
+                count_of(aggregation)
+            );
+            print_aggregation_list(
+                &format!("{}  ", indent),
+                &children(aggregation),
+            );
+        }
+        "top_hits" => {
+            let hits = &aggregation["hits"];
+            println!("{}{}: {} hits", indent, label, count_of(hits));
+            if let Some(hits) = hits["hits"].as_array() {
+                for hit in hits {
+                    println!("{}  {}", indent, plain_text(&hit["id"]));
+                }
+            }
+        }
+        _ if !aggregation["value"].is_null() => {
+            println!(
+                "{}{}: {}",
+                indent,
+                label,
+                plain_text(&aggregation["value"])
+            )
+        }
+        _ => {
+            println!(
+                "{}{}",
+                indent,
+                to_string(aggregation).unwrap_or_default()
+            )
+        }
+    }
+}
+
+fn print_aggregation_list(indent: &str, aggregations: &[Value]) {
+    for aggregation in aggregations {
+        print_aggregation(indent, aggregation)
+    }
+}
+
+// Draw the aggregations from a query response as tables, sparklines and
+// indented trees.
+pub fn print_aggregations(aggregations: &[Value]) {
+    print_aggregation_list("", aggregations)
+}
+
+fn csv_rows(
+    parent: &str,
+    aggregations: &[Value],
+    rows: &mut Vec<Vec<String>>,
+) {
+    for aggregation in aggregations {
+        let label = if parent.is_empty() {
+            aggregation_label(aggregation)
+        } else {
+            format!("{} > {}", parent, aggregation_label(aggregation))
+        };
+        if let Some(buckets) = aggregation["results"].as_array() {
+            for bucket in buckets {
+                let key = bucket_key(bucket);
+                rows.push(vec![
+                    label.clone(),
+                    key.clone(),
+                    count_of(bucket).to_string(),
+                    String::new(),
+                ]);
+                csv_rows(
+                    &format!("{} > {}", label, key),
+                    &children(bucket),
+                    rows,
+                );
+            }
+        } else {
+            let matching = if aggregation["matching_results"].is_null() {
+                String::new()
+            } else {
+                count_of(aggregation).to_string()
+            };
+            rows.push(vec![
+                label.clone(),
+                String::new(),
+                matching,
+                plain_text(&aggregation["value"]),
+            ]);
+            csv_rows(&label, &children(aggregation), rows);
+        }
+    }
+}
+
+// One CSV row for every bucket and metric, nested ones named by the path
+// of aggregations and keys leading to them.
+pub fn print_aggregations_csv(aggregations: &[Value]) {
+    let mut rows = Vec::new();
+    csv_rows("", aggregations, &mut rows);
+    println!("aggregation,key,matching_results,value");
+    for row in rows {
+        let cells: Vec<String> = row.into_iter()
+                                    .map(|cell| csv_cell(&Value::String(cell)))
+                                    .collect();
+        println!("{}", csv_row(&cells));
+    }
+}
@@ -0,0 +1,192 @@
+use hyper;
+use hyper::Client;
+use hyper::header::{Authorization, Basic, ContentType};
+use hyper::method::Method;
+use hyper::net::HttpsConnector;
+use hyper::status::StatusCode;
+use hyper_rustls::TlsClient;
+use serde_json;
+use serde_json::{Map, Value};
+use std::fmt;
+use std::io;
+use std::io::Read;
+use wdsapi::collection::NewCollection;
+use wdsapi::common::Credentials;
+
+// wdsapi has no calls to change an existing resource, so those requests
+// are sent from here, over the same hyper and hyper-rustls wdsapi uses.
+
+// The API version these requests are written against.
+const VERSION: &str = "2018-12-03";
+
+#[derive(Debug)]
+pub enum RequestError {
+    Hyper(hyper::Error),
+    Io(io::Error),
+    SerdeJson(serde_json::Error),
+    Service(StatusCode, Value),
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RequestError::Hyper(ref e) => write!(f, "{}", e),
+            RequestError::Io(ref e) => write!(f, "{}", e),
+            RequestError::SerdeJson(ref e) => write!(f, "{}", e),
+            RequestError::Service(ref status, ref body) => {
+                write!(f, "{} {}", status, body)
+            }
+        }
+    }
+}
+
+impl From<hyper::Error> for RequestError {
+    fn from(e: hyper::Error) -> RequestError {
+        RequestError::Hyper(e)
+    }
+}
+
+impl From<io::Error> for RequestError {
+    fn from(e: io::Error) -> RequestError {
+        RequestError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for RequestError {
+    fn from(e: serde_json::Error) -> RequestError {
+        RequestError::SerdeJson(e)
+    }
+}
+
+// Send `body` to `path` under the service's /v1, returning the response
+// body, or the status and body when the service reports an error.
+fn send(
+    creds: &Credentials,
+    method: Method,
+    path: &str,
+    body: &Value,
+) -> Result<Value, RequestError> {
+    let client = Client::with_connector(HttpsConnector::new(TlsClient::new()));
+    let url = format!("{}/v1/{}?version={}", creds.url, path, VERSION);
+    let body = serde_json::to_string(body)?;
+    let mut response = client
+        .request(method, &url)
+        .header(Authorization(Basic {
+            username: creds.username.clone(),
+            password: Some(creds.password.clone()),
+        }))
+        .header(ContentType::json())
+        .body(body.as_str())
+        .send()?;
+    let mut text = String::new();
+    response.read_to_string(&mut text)?;
+    let value = if text.is_empty() {
+        Value::Null
+    } else {
+        serde_json::from_str(&text)?
+    };
+    if response.status.is_success() {
+        Ok(value)
+    } else {
+        Err(RequestError::Service(response.status, value))
+    }
+}
+
+fn put(
+    creds: &Credentials,
+    path: &str,
+    body: &Value,
+) -> Result<Value, RequestError> {
+    send(creds, Method::Put, path, body)
+}
+
+// Replace a configuration with `configuration`, which must have a name.
+pub fn update_configuration(
+    creds: &Credentials,
+    env_id: &str,
+    configuration_id: &str,
+    configuration: &Value,
+) -> Result<Value, RequestError> {
+    put(
+        creds,
+        &format!(
+            "environments/{}/configurations/{}",
+            env_id,
+            configuration_id
+        ),
+        configuration,
+    )
+}
+
+// Replace a collection's name, description and configuration.
+pub fn update_collection(
+    creds: &Credentials,
+    env_id: &str,
+    collection_id: &str,
+    options: &NewCollection,
+) -> Result<Value, RequestError> {
+    let mut body = Map::new();
+    body.insert("name".to_string(), Value::String(options.name.clone()));
+    if let Some(ref description) = options.description {
+        body.insert(
+            "description".to_string(),
+            Value::String(description.clone()),
+        );
+    }
+    if let Some(ref configuration_id) = options.configuration_id {
+        body.insert(
+            "configuration_id".to_string(),
+            Value::String(configuration_id.clone()),
+        );
+    }
+    put(
+        creds,
+        &format!("environments/{}/collections/{}", env_id, collection_id),
+        &Value::Object(body),
+    )
+}
+
+fn environment_body(
+    name: &str,
+    description: Option<String>,
+    size: &str,
+) -> Value {
+    let mut body = Map::new();
+    body.insert("name".to_string(), Value::String(name.to_string()));
+    if let Some(description) = description {
+        body.insert("description".to_string(), Value::String(description));
+    }
+    body.insert("size".to_string(), Value::String(size.to_string()));
+    Value::Object(body)
+}
+
+// Create an environment of a named size, like XS.
+pub fn create_environment(
+    creds: &Credentials,
+    name: &str,
+    description: Option<String>,
+    size: &str,
+) -> Result<Value, RequestError> {
+    send(
+        creds,
+        Method::Post,
+        "environments",
+        &environment_body(name, description, size),
+    )
+}
+
+// Change an environment's size, which the service only ever increases.
+// The name and description are sent as they are.
+pub fn update_environment(
+    creds: &Credentials,
+    env_id: &str,
+    name: &str,
+    description: Option<String>,
+    size: &str,
+) -> Result<Value, RequestError> {
+    put(
+        creds,
+        &format!("environments/{}", env_id),
+        &environment_body(name, description, size),
+    )
+}
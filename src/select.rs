@@ -1,6 +1,7 @@
 use clap;
 use info::{DiscoveryServiceInfo, EnvironmentInfo};
 use serde_json::Value;
+use std;
 
 pub fn read_only_environment(info: &DiscoveryServiceInfo) -> EnvironmentInfo {
     let read_only: Vec<EnvironmentInfo> =
@@ -64,7 +65,18 @@ pub fn collection_with_name(env: &EnvironmentInfo, name: &str) -> Value {
         |i| i["name"].as_str() == Some(name)
     })
                            .collect();
-    assert_eq!(f.len(), 1, "No collection matched {}", name);
+    if f.is_empty() {
+        println!("No collection matched {}", name);
+        std::process::exit(1)
+    }
+    if f.len() > 1 {
+        println!(
+            "{} collections are named {}; select one with --with-id",
+            f.len(),
+            name
+        );
+        std::process::exit(1)
+    }
     f.first()
      .expect("Internal error: count=1, but no last!?")
      .clone()
@@ -157,3 +169,43 @@ pub fn select_configuration(
         newest_configuration(env_info)
     }
 }
+
+// A configuration chosen by a selector given as one string: "newest",
+// "oldest", "name:<name>" or "id:<id>". None when the string is not a
+// selector.
+pub fn configuration_with_selector(
+    env_info: &EnvironmentInfo,
+    selector: &str,
+) -> Option<Value> {
+    if selector == "newest" {
+        Some(newest_configuration(env_info))
+    } else if selector == "oldest" {
+        Some(oldest_configuration(env_info))
+    } else if selector.starts_with("name:") {
+        Some(configuration_with_name(env_info, &selector[5..]))
+    } else if selector.starts_with("id:") {
+        Some(configuration_with_id(env_info, &selector[3..]))
+    } else {
+        None
+    }
+}
+
+// A collection chosen by a selector given as one string: "newest",
+// "oldest", "name:<name>" or "id:<id>". None when the string is not a
+// selector.
+pub fn collection_with_selector(
+    env_info: &EnvironmentInfo,
+    selector: &str,
+) -> Option<Value> {
+    if selector == "newest" {
+        Some(newest_collection(env_info))
+    } else if selector == "oldest" {
+        Some(oldest_collection(env_info))
+    } else if selector.starts_with("name:") {
+        Some(collection_with_name(env_info, &selector[5..]))
+    } else if selector.starts_with("id:") {
+        Some(collection_with_id(env_info, &selector[3..]))
+    } else {
+        None
+    }
+}
@@ -0,0 +1,209 @@
+use clap;
+use info::discovery_service_info;
+use query::{TABLE_FIELDS, print_response};
+use rustyline::Editor;
+use rustyline::error::ReadlineError;
+use select::{read_only_environment, select_collection, writable_environment};
+use serde_json::Value;
+use std::env;
+use std::path::PathBuf;
+use wdsapi::common::{Credentials, QueryParams};
+use wdsapi::query;
+
+const HELP: &str = "\
+query <text>          run a query; q for short
+nlq <text>            run a natural language query
+filter [<text>]       set the filter, or clear it, and run
+aggregation [<text>]  set the aggregation, or clear it, and run
+count <n>             set how many results to show, and run
+fields [<a,b,c>]      set the table columns, or go back to id,score
+output table|json     show results as a table or as the full JSON
+use <name>            switch to the collection with this name
+collections           list the collections
+show                  show the current settings
+help                  show this
+exit                  leave the shell; also Ctrl-D";
+
+// Everything a query in the shell is built from.
+struct Session {
+    collection: Value,
+    query: Option<String>,
+    natural_language_query: Option<String>,
+    filter: Option<String>,
+    aggregation: Option<String>,
+    count: u64,
+    fields: Vec<String>,
+    table: bool,
+}
+
+fn optional(text: &str) -> Option<String> {
+    if text.is_empty() {
+        None
+    } else {
+        Some(text.to_string())
+    }
+}
+
+// HOME, or USERPROFILE on Windows.
+fn history_file() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .map(|home| PathBuf::from(home).join(".wdscli_history"))
+}
+
+fn run_query(creds: &Credentials, env_id: &str, session: &Session) {
+    let fields: Vec<&str> = session.fields.iter().map(|f| f.as_str()).collect();
+    let params = QueryParams {
+        filter: session.filter.clone(),
+        query: session.query.clone(),
+        natural_language_query: session.natural_language_query.clone(),
+        passages: Some(false),
+        aggregation: session.aggregation.clone(),
+        count: session.count,
+        return_hierarchy: if session.table {
+            Some(fields.join(","))
+        } else {
+            None
+        },
+        offset: None,
+        sort: None,
+    };
+    match query::query(
+        creds,
+        env_id,
+        session.collection["collection_id"].as_str().unwrap_or(""),
+        params,
+    ) {
+        Ok(response) => {
+            print_response(&response, session.table, &fields, "text")
+        }
+        Err(e) => println!("Query failed {}", e),
+    }
+}
+
+fn show_session(session: &Session) {
+    println!("collection   {}", session.collection["name"]);
+    println!("query        {:?}", session.query);
+    println!("nlq          {:?}", session.natural_language_query);
+    println!("filter       {:?}", session.filter);
+    println!("aggregation  {:?}", session.aggregation);
+    println!("count        {}", session.count);
+    println!("fields       {}", session.fields.join(","));
+    println!("output       {}", if session.table { "table" } else { "json" });
+}
+
+// Service information is loaded once; each command after that is a single
+// query.
+pub fn shell(creds: Credentials, matches: &clap::ArgMatches) {
+    let info = discovery_service_info(creds);
+    let env_info = if matches.is_present("read-only") {
+        read_only_environment(&info)
+    } else {
+        writable_environment(&info)
+    };
+    let env_id = &env_info.environment_id;
+    let mut session = Session {
+        collection: select_collection(&env_info, matches),
+        query: None,
+        natural_language_query: None,
+        filter: None,
+        aggregation: None,
+        count: 10,
+        fields: TABLE_FIELDS.iter().map(|f| f.to_string()).collect(),
+        table: true,
+    };
+
+    let mut editor = Editor::<()>::new();
+    if let Some(ref path) = history_file() {
+        let _ = editor.load_history(path);
+    }
+    println!("Type help for the commands; exit or Ctrl-D to leave.");
+    loop {
+        let prompt = format!(
+            "{}> ",
+            session.collection["name"].as_str().unwrap_or("")
+        );
+        let line = match editor.readline(&prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => {
+                println!("Failed to read input {}", e);
+                break;
+            }
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        editor.add_history_entry(line);
+        let mut parts = line.splitn(2, char::is_whitespace);
+        let command = parts.next().unwrap_or("");
+        let argument = parts.next().unwrap_or("").trim();
+        match command {
+            "query" | "q" => {
+                session.query = optional(argument);
+                session.natural_language_query = None;
+                run_query(&info.creds, env_id, &session)
+            }
+            "nlq" => {
+                session.natural_language_query = optional(argument);
+                session.query = None;
+                run_query(&info.creds, env_id, &session)
+            }
+            "filter" => {
+                session.filter = optional(argument);
+                run_query(&info.creds, env_id, &session)
+            }
+            "aggregation" => {
+                session.aggregation = optional(argument);
+                run_query(&info.creds, env_id, &session)
+            }
+            "count" => {
+                match argument.parse() {
+                    Ok(count) => {
+                        session.count = count;
+                        run_query(&info.creds, env_id, &session)
+                    }
+                    Err(_) => println!("count needs a number"),
+                }
+            }
+            "fields" => {
+                session.fields = if argument.is_empty() {
+                    TABLE_FIELDS.iter().map(|f| f.to_string()).collect()
+                } else {
+                    argument.split(',').map(|f| f.trim().to_string()).collect()
+                }
+            }
+            "output" => {
+                match argument {
+                    "table" => session.table = true,
+                    "json" => session.table = false,
+                    _ => println!("output is either table or json"),
+                }
+            }
+            "use" => {
+                match env_info.collections.iter().find(|c| {
+                    c["name"].as_str() == Some(argument)
+                }) {
+                    Some(collection) => session.collection = collection.clone(),
+                    None => println!("No collection named {}", argument),
+                }
+            }
+            "collections" => {
+                for collection in &env_info.collections {
+                    println!("{}", collection["name"])
+                }
+            }
+            "show" => show_session(&session),
+            "help" => println!("{}", HELP),
+            "exit" | "quit" => break,
+            _ => println!("Unknown command {}; try help", command),
+        }
+    }
+    if let Some(ref path) = history_file() {
+        if let Err(e) = editor.save_history(path) {
+            println!("Failed to save history {}", e);
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use info::discovery_service_info;
+use request;
+use serde_json::Value;
+use std;
+use std::fmt;
+use wdsapi::common::Credentials;
+use wdsapi::environment;
+use wdsapi::environment::NewEnvironment;
+
+// The environment sizes the service accepts, smallest first, as listed
+// for "size" under "Create an environment" in the Discovery v1 API
+// reference. What each size holds depends on the plan; the service
+// reports the limits of an existing environment as its index_capacity.
+const SIZES: &[(&str, &str)] = &[
+    ("LT", "Lite"),
+    ("XS", "Extra Small"),
+    ("S", "Small"),
+    ("MS", "Medium Small"),
+    ("M", "Medium"),
+    ("ML", "Medium Large"),
+    ("L", "Large"),
+    ("XL", "Extra Large"),
+    ("XXL", "Extra Extra Large"),
+    ("XXXL", "Extra Extra Extra Large"),
+];
+
+// A size as given on the command line or in a manifest. Names are sent
+// to the service as they are; a number is sent through wdsapi, which
+// takes the size as a number, as wdscli always has.
+#[derive(Clone, Copy)]
+pub enum Size {
+    Named(&'static str),
+    Number(u64),
+}
+
+impl fmt::Display for Size {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Size::Named(code) => write!(f, "{}", code),
+            Size::Number(number) => write!(f, "{}", number),
+        }
+    }
+}
+
+impl Size {
+    // Whether an environment's reported size is this size.
+    pub fn is(&self, reported: &Value) -> bool {
+        match *self {
+            Size::Named(code) => {
+                reported.as_str().map_or(
+                    false,
+                    |r| r.eq_ignore_ascii_case(code),
+                )
+            }
+            Size::Number(number) => reported.as_u64() == Some(number),
+        }
+    }
+}
+
+fn named(text: &str) -> Option<&'static str> {
+    SIZES.iter()
+         .find(|&&(code, _)| code.eq_ignore_ascii_case(text))
+         .map(|&(code, _)| code)
+}
+
+fn known_sizes() -> String {
+    let codes: Vec<&str> = SIZES.iter().map(|&(code, _)| code).collect();
+    codes.join(", ")
+}
+
+// Parse a size name like XS, or a number; exits for anything else.
+pub fn parse_size(text: &str) -> Size {
+    if let Some(code) = named(text) {
+        return Size::Named(code);
+    }
+    match text.parse::<u64>() {
+        Ok(number) => Size::Number(number),
+        Err(_) => {
+            println!(
+                "Unknown environment size {}; expected one of {}",
+                text,
+                known_sizes()
+            );
+            std::process::exit(1)
+        }
+    }
+}
+
+// Where a size name comes in the list, for telling larger from smaller.
+pub fn position(size: &Value) -> Option<usize> {
+    size.as_str().and_then(|text| {
+        SIZES.iter().position(|&(code, _)| code.eq_ignore_ascii_case(text))
+    })
+}
+
+// Create an environment, sending the size the way its form needs.
+pub fn create_with_size(
+    creds: &Credentials,
+    name: &str,
+    description: Option<String>,
+    size: Size,
+) -> Result<Value, String> {
+    match size {
+        Size::Named(code) => {
+            request::create_environment(creds, name, description, code)
+                .map_err(|e| e.to_string())
+        }
+        Size::Number(number) => {
+            let env_options = NewEnvironment {
+                name: name.to_string(),
+                description: description,
+                size: number,
+            };
+            environment::create(creds, &env_options).map_err(|e| e.to_string())
+        }
+    }
+}
+
+// The sizes, each with the environments of that size and the limits the
+// service reports for them.
+pub fn list_environment_sizes(creds: Credentials) {
+    let environments: Vec<Value> = discovery_service_info(creds)
+        .environments
+        .into_iter()
+        .map(|env| env.environment)
+        .collect();
+    let report = |environment: &Value| {
+        let capacity = &environment["index_capacity"];
+        println!(
+            "         ↳ {} is this size: {} disk, {} memory, {} documents, \
+             {} collections",
+            environment["name"],
+            capacity["disk_usage"]["total"].as_str().unwrap_or("?"),
+            capacity["memory_usage"]["total"].as_str().unwrap_or("?"),
+            capacity["documents"]["maximum_allowed"],
+            capacity["collections"]["maximum_allowed"]
+        );
+    };
+    for &(code, name) in SIZES {
+        println!("{:<5} {}", code, name);
+        for environment in &environments {
+            if Size::Named(code).is(&environment["size"]) {
+                report(environment)
+            }
+        }
+    }
+    // Older environments report their size as a number.
+    for environment in &environments {
+        if let Some(number) = environment["size"].as_u64() {
+            println!("{:<5} (a number, from an older API version)", number);
+            report(environment)
+        }
+    }
+}
@@ -0,0 +1,246 @@
+use clap;
+use serde_json;
+use serde_json::Value;
+use serde_yaml;
+use std;
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use toml;
+
+// Read JSON, YAML or TOML, chosen by the file extension; anything other
+// than .yaml, .yml or .toml is read as JSON.
+pub fn read_document(filename: &str) -> Value {
+    let mut file = File::open(filename).expect(
+        &format!("Failed to read {}", filename),
+    );
+    let extension = Path::new(filename)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    match extension.as_str() {
+        "yaml" | "yml" => {
+            serde_yaml::from_reader(file).expect(
+                &format!("Failed to parse YAML in {}", filename),
+            )
+        }
+        "toml" => {
+            let mut text = String::new();
+            file.read_to_string(&mut text).expect(
+                &format!("Failed to read {}", filename),
+            );
+            toml::from_str(&text).expect(
+                &format!("Failed to parse TOML in {}", filename),
+            )
+        }
+        _ => {
+            serde_json::from_reader(file).expect(
+                &format!("Failed to parse JSON in {}", filename),
+            )
+        }
+    }
+}
+
+// Replace ${VAR} with the value of the environment variable VAR; $$ is a
+// literal $.
+fn substitute_string(text: &str) -> String {
+    let mut result = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('$') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if rest.starts_with("$$") {
+            result.push('$');
+            rest = &rest[2..];
+        } else if rest.starts_with("${") && rest.contains('}') {
+            let end = rest.find('}').unwrap();
+            let name = &rest[2..end];
+            match env::var(name) {
+                Ok(value) => result.push_str(&value),
+                Err(_) => {
+                    println!("Environment variable {} is not set", name);
+                    std::process::exit(1)
+                }
+            }
+            rest = &rest[end + 1..];
+        } else {
+            result.push('$');
+            rest = &rest[1..];
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+// Substitute environment variables in every string in the document.
+pub fn substitute(value: &mut Value) {
+    match *value {
+        Value::String(ref mut s) => *s = substitute_string(s),
+        Value::Array(ref mut items) => {
+            for item in items.iter_mut() {
+                substitute(item)
+            }
+        }
+        Value::Object(ref mut fields) => {
+            for (_, field) in fields.iter_mut() {
+                substitute(field)
+            }
+        }
+        _ => (),
+    }
+}
+
+// Set the value at a dotted path like "enrichments.0.options.language",
+// creating objects along the way as needed.
+pub fn set_path(value: &mut Value, path: &str, new_value: Value) {
+    let mut target = value;
+    for key in path.split('.') {
+        let current = target;
+        target = match *current {
+            Value::Array(ref mut items) => {
+                let index: usize = key.parse().expect(&format!(
+                    "Array index expected in {} at {}",
+                    path,
+                    key
+                ));
+                items.get_mut(index).expect(&format!(
+                    "Array index {} is out of range in {}",
+                    index,
+                    path
+                ))
+            }
+            _ => {
+                if !current.is_object() {
+                    *current = Value::Object(serde_json::Map::new());
+                }
+                current.as_object_mut()
+                       .unwrap()
+                       .entry(key.to_string())
+                       .or_insert(Value::Null)
+            }
+        }
+    }
+    *target = new_value;
+}
+
+// The value at a dotted path like "enriched_text.sentiment.label", or
+// Null when there is nothing there. A numeric key indexes into an array;
+// any other key is looked up in each element, giving an array of what it
+// found, so "enriched_text.entities.text" is every entity's text.
+pub fn get_path(value: &Value, path: &str) -> Value {
+    let mut keys = path.splitn(2, '.');
+    let key = keys.next().unwrap();
+    let rest = keys.next();
+    let target = match *value {
+        Value::Array(ref items) => {
+            match key.parse::<usize>() {
+                Ok(index) => items.get(index).unwrap_or(&Value::Null),
+                Err(_) => {
+                    return Value::Array(
+                        items.iter().map(|item| get_path(item, path)).collect(),
+                    )
+                }
+            }
+        }
+        _ => &value[key],
+    };
+    match rest {
+        Some(rest) => get_path(target, rest),
+        None => target.clone(),
+    }
+}
+
+// Parse the value of a --set; JSON when that works, otherwise a string.
+fn set_value(text: &str) -> Value {
+    serde_json::from_str(text)
+        .unwrap_or_else(|_| Value::String(text.to_string()))
+}
+
+// Read the file named by the "configuration" argument, substitute
+// environment variables and apply each --set.
+pub fn render_configuration(matches: &clap::ArgMatches) -> Value {
+    let filename = matches.value_of("configuration").unwrap();
+    let mut config = read_document(filename);
+    substitute(&mut config);
+    if let Some(sets) = matches.values_of("set") {
+        for set in sets {
+            let mut parts = set.splitn(2, '=');
+            let path = parts.next().unwrap();
+            match parts.next() {
+                Some(text) => set_path(&mut config, path, set_value(text)),
+                None => {
+                    println!("Expected key=value for --set, not {}", set);
+                    std::process::exit(1)
+                }
+            }
+        }
+    }
+    config
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{set_path, substitute_string};
+    use serde_json::{Value, from_str};
+    use std::env;
+
+    fn json(text: &str) -> Value {
+        from_str(text).unwrap()
+    }
+
+    #[test]
+    fn substitute_string_replaces_variables() {
+        env::set_var("WDSCLI_TEST_LANGUAGE", "en");
+        assert_eq!(
+            substitute_string("lang ${WDSCLI_TEST_LANGUAGE}!"),
+            "lang en!"
+        );
+        assert_eq!(
+            substitute_string("${WDSCLI_TEST_LANGUAGE}${WDSCLI_TEST_LANGUAGE}"),
+            "enen"
+        );
+    }
+
+    #[test]
+    fn substitute_string_leaves_other_dollars() {
+        assert_eq!(substitute_string("costs $$5"), "costs $5");
+        assert_eq!(substitute_string("$${X}"), "${X}");
+        assert_eq!(substitute_string("$5 and $"), "$5 and $");
+        assert_eq!(substitute_string("${unclosed"), "${unclosed");
+        assert_eq!(substitute_string("no variables"), "no variables");
+    }
+
+    #[test]
+    fn set_path_replaces_and_creates_fields() {
+        let mut config = json(r#"{"name": "old", "options": {"a": 1}}"#);
+        set_path(&mut config, "name", json(r#""new""#));
+        set_path(&mut config, "options.b.c", json("true"));
+        assert_eq!(
+            config,
+            json(r#"{"name": "new", "options": {"a": 1, "b": {"c": true}}}"#)
+        );
+    }
+
+    #[test]
+    fn set_path_indexes_arrays() {
+        let mut config = json(r#"{"enrichments": [{"options": {}}]}"#);
+        set_path(
+            &mut config,
+            "enrichments.0.options.language",
+            json(r#""fr""#),
+        );
+        assert_eq!(
+            config,
+            json(r#"{"enrichments": [{"options": {"language": "fr"}}]}"#)
+        );
+    }
+
+    #[test]
+    fn set_path_replaces_scalars_on_the_way() {
+        let mut config = json(r#"{"options": 3}"#);
+        set_path(&mut config, "options.language", json(r#""de""#));
+        assert_eq!(config, json(r#"{"options": {"language": "de"}}"#));
+    }
+}
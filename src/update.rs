@@ -0,0 +1,220 @@
+use clap;
+use confirm::{confirm, describe_configuration};
+use diff::{diff, print_differences, without_server_fields};
+use info::{EnvironmentInfo, discovery_service_info};
+use request;
+use select::{configuration_with_id, configuration_with_selector,
+             select_collection, select_configuration, writable_environment};
+use serde_json::{Value, to_string_pretty};
+use size::{Size, parse_size, position};
+use std;
+use template::render_configuration;
+use validate::validate_or_exit;
+use wdsapi::collection::NewCollection;
+use wdsapi::common::Credentials;
+use wdsapi::configuration;
+use wdsapi::environment;
+use wait::{Poll, wait_for_status};
+
+pub fn update_configuration(creds: Credentials, matches: &clap::ArgMatches) {
+    let mut local = without_server_fields(&render_configuration(matches));
+    let info = discovery_service_info(creds);
+    let env_info = writable_environment(&info);
+    let env_id = env_info.environment_id.clone();
+    let selected = select_configuration(&env_info, matches);
+    let configuration_id = match selected["configuration_id"].as_str() {
+        Some(id) => id,
+        None => {
+            println!("No configuration matched");
+            std::process::exit(1)
+        }
+    };
+
+    let current = match configuration::detail(
+        &info.creds,
+        &env_id,
+        configuration_id,
+    ) {
+        Ok(response) => without_server_fields(&response),
+        Err(e) => {
+            println!("Failed to lookup configuration {}", e);
+            std::process::exit(1)
+        }
+    };
+    // The service requires a name; keep the current one unless the file
+    // has its own.
+    if local.get("name").is_none() {
+        local["name"] = current["name"].clone();
+    }
+    validate_or_exit(&local, matches);
+
+    let differences = diff(&current, &local);
+    if differences.is_empty() {
+        println!(
+            "Configuration {} already matches {}",
+            selected["name"],
+            matches.value_of("configuration").unwrap()
+        );
+        return;
+    }
+    print_differences(&differences);
+    confirm(
+        matches,
+        "update a configuration",
+        &[describe_configuration(&selected)],
+    );
+
+    match request::update_configuration(
+        &info.creds,
+        &env_id,
+        configuration_id,
+        &local,
+    ) {
+        Ok(response) => {
+            println!(
+                "{}",
+                to_string_pretty(&response).expect(
+                    "Internal error: failed to format \
+                                  update_configuration response",
+                )
+            )
+        }
+        Err(e) => {
+            println!("Failed to update configuration {}", e);
+            std::process::exit(1)
+        }
+    }
+}
+
+fn print_collection(label: &str, env_info: &EnvironmentInfo, col: &Value) {
+    let config = configuration_with_id(
+        env_info,
+        col["configuration_id"].as_str().unwrap_or(""),
+    );
+    println!(
+        "{}: {} ↳ {}, description {}",
+        label,
+        col["name"],
+        config["name"],
+        col["description"]
+    );
+}
+
+pub fn update_collection(creds: Credentials, matches: &clap::ArgMatches) {
+    let info = discovery_service_info(creds);
+    let env_info = writable_environment(&info);
+    let env_id = env_info.environment_id.clone();
+    let before = select_collection(&env_info, matches);
+    let col_id = before["collection_id"].as_str().expect(
+        "Internal error: missing collection_id",
+    );
+    let configuration_id = match matches.value_of("configuration") {
+        Some(selector) => {
+            match configuration_with_selector(&env_info, selector) {
+                Some(ref selected) if selected.is_object() => {
+                    selected["configuration_id"].clone()
+                }
+                _ => {
+                    println!(
+                        "Expected newest, oldest, name:<name> or id:<id> \
+                         naming an existing configuration for \
+                         --configuration, not {}",
+                        selector
+                    );
+                    std::process::exit(1)
+                }
+            }
+        }
+        None => before["configuration_id"].clone(),
+    };
+
+    // The service replaces the collection's settings; anything not being
+    // changed is sent as it is now.
+    let col_options = NewCollection {
+        name: matches.value_of("name")
+                     .or(before["name"].as_str())
+                     .unwrap_or("")
+                     .to_string(),
+        description: matches.value_of("description")
+                            .or(before["description"].as_str())
+                            .map(|d| d.to_string()),
+        configuration_id: configuration_id.as_str().map(|c| c.to_string()),
+    };
+    print_collection("Before", &env_info, &before);
+    match request::update_collection(
+        &info.creds,
+        &env_id,
+        col_id,
+        &col_options,
+    ) {
+        Ok(after) => print_collection("After", &env_info, &after),
+        Err(e) => {
+            println!("Failed to update collection {}", e);
+            std::process::exit(1)
+        }
+    }
+}
+
+pub fn resize_environment(creds: Credentials, matches: &clap::ArgMatches) {
+    let info = discovery_service_info(creds);
+    let env_info = writable_environment(&info);
+    let env = &env_info.environment;
+    let code = match parse_size(matches.value_of("size").unwrap()) {
+        Size::Named(code) => code,
+        Size::Number(_) => {
+            println!("resize-environment takes a size name, like S or M");
+            std::process::exit(1)
+        }
+    };
+    // The service only ever grows an environment. An environment that
+    // reports its size as a number is left for the service to check.
+    let wanted = position(&Value::String(code.to_string()));
+    if let Some(current) = position(&env["size"]) {
+        if wanted <= Some(current) {
+            println!(
+                "Environment {} is already size {}; it can only be made \
+                 larger",
+                env["name"],
+                env["size"]
+            );
+            std::process::exit(1)
+        }
+    }
+    confirm(
+        matches,
+        "resize an environment",
+        &[format!("{} from {} to {}", env["name"], env["size"], code)],
+    );
+
+    let env_id = &env_info.environment_id;
+    match request::update_environment(
+        &info.creds,
+        env_id,
+        env["name"].as_str().unwrap_or(""),
+        env["description"].as_str().map(|d| d.to_string()),
+        code,
+    ) {
+        Ok(response) => {
+            println!(
+                "{}",
+                to_string_pretty(&response).expect(
+                    "Internal error: failed to format \
+                                  update_environment response",
+                )
+            )
+        }
+        Err(e) => {
+            println!("Failed to resize environment {}", e);
+            std::process::exit(1)
+        }
+    }
+    if !wait_for_status(
+        &mut Poll::new(matches),
+        "Environment",
+        &["active", "available"],
+        || environment::detail(&info.creds, env_id),
+    )
+    {
+        std::process::exit(1)
+    }
+}
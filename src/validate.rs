@@ -0,0 +1,239 @@
+use clap;
+use serde_json::{Value, from_str};
+use std;
+use template::render_configuration;
+
+// A JSON Schema for the Discovery configuration format. Only the parts of
+// JSON Schema the file uses are understood here: type, enum, minimum,
+// required, properties, additionalProperties, items, allOf, if/then and
+// $ref to "#/definitions/...".
+const SCHEMA: &str = include_str!("configuration_schema.json");
+
+fn type_name(value: &Value) -> &'static str {
+    match *value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(ref n) if n.is_u64() || n.is_i64() => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn type_matches(expected: &str, value: &Value) -> bool {
+    let actual = type_name(value);
+    actual == expected || (expected == "number" && actual == "integer")
+}
+
+fn check(
+    schema: &Value,
+    root: &Value,
+    value: &Value,
+    path: &str,
+    errors: &mut Vec<String>,
+) {
+    if let Some(reference) = schema["$ref"].as_str() {
+        let name = reference.trim_start_matches("#/definitions/");
+        return check(&root["definitions"][name], root, value, path, errors);
+    }
+    if let Some(expected) = schema["type"].as_str() {
+        if !type_matches(expected, value) {
+            errors.push(format!(
+                "{}: expected {}, found {}",
+                path,
+                expected,
+                type_name(value)
+            ));
+            return;
+        }
+    }
+    if let Some(allowed) = schema["enum"].as_array() {
+        if !allowed.contains(value) {
+            let names: Vec<String> =
+                allowed.iter().map(|a| a.to_string()).collect();
+            errors.push(format!(
+                "{}: {} is not one of {}",
+                path,
+                value,
+                names.join(", ")
+            ));
+        }
+    }
+    if let (Some(minimum), Some(number)) =
+        (schema["minimum"].as_f64(), value.as_f64())
+    {
+        if number < minimum {
+            errors.push(format!(
+                "{}: {} is less than the minimum {}",
+                path,
+                value,
+                minimum
+            ));
+        }
+    }
+    if let Some(fields) = value.as_object() {
+        if let Some(required) = schema["required"].as_array() {
+            for name in required.iter().filter_map(|r| r.as_str()) {
+                if !fields.contains_key(name) {
+                    errors.push(
+                        format!("{}: missing required field {}", path, name),
+                    );
+                }
+            }
+        }
+        for (name, field) in fields {
+            let field_path = format!("{}.{}", path, name);
+            let field_schema = &schema["properties"][name];
+            if !field_schema.is_null() {
+                check(field_schema, root, field, &field_path, errors)
+            } else if schema["additionalProperties"] == Value::Bool(false) {
+                errors.push(format!("{}: unknown field", field_path));
+            }
+        }
+    }
+    if let Some(items) = value.as_array() {
+        if !schema["items"].is_null() {
+            for (i, item) in items.iter().enumerate() {
+                let item_path = format!("{}[{}]", path, i);
+                check(&schema["items"], root, item, &item_path, errors)
+            }
+        }
+    }
+    if let Some(all) = schema["allOf"].as_array() {
+        for sub_schema in all {
+            if sub_schema["if"].is_null() {
+                check(sub_schema, root, value, path, errors)
+            } else {
+                let mut failures = Vec::new();
+                check(&sub_schema["if"], root, value, path, &mut failures);
+                if failures.is_empty() {
+                    check(&sub_schema["then"], root, value, path, errors)
+                }
+            }
+        }
+    }
+}
+
+// Every problem found in the configuration, each starting with the JSON
+// path where it was found.
+pub fn validate(configuration: &Value) -> Vec<String> {
+    let schema: Value = from_str(SCHEMA).expect(
+        "Internal error: invalid configuration schema",
+    );
+    let mut errors = Vec::new();
+    check(&schema, &schema, configuration, "$", &mut errors);
+    errors
+}
+
+// Validate before sending a configuration to the service, unless
+// --no-validate was given. Exits when there are problems.
+pub fn validate_or_exit(configuration: &Value, matches: &clap::ArgMatches) {
+    if matches.is_present("no-validate") {
+        return;
+    }
+    let errors = validate(configuration);
+    if !errors.is_empty() {
+        for error in &errors {
+            println!("{}", error);
+        }
+        println!(
+            "Configuration has {} problems; use --no-validate to send \
+             it anyway",
+            errors.len()
+        );
+        std::process::exit(1)
+    }
+}
+
+pub fn validate_configuration(matches: &clap::ArgMatches) {
+    let configuration = render_configuration(matches);
+    let errors = validate(&configuration);
+    for error in &errors {
+        println!("{}", error);
+    }
+    if errors.is_empty() {
+        println!("{} is valid", matches.value_of("configuration").unwrap());
+    } else {
+        std::process::exit(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check;
+    use serde_json::{Value, from_str};
+
+    const TEST_SCHEMA: &str = r##"{
+        "definitions": {
+            "step": {
+                "type": "object",
+                "required": ["kind"],
+                "properties": {
+                    "kind": {"type": "string", "enum": ["split", "join"]},
+                    "size": {"type": "integer", "minimum": 1}
+                },
+                "additionalProperties": false,
+                "allOf": [{
+                    "if": {"properties": {"kind": {"enum": ["split"]}}},
+                    "then": {"required": ["size"]}
+                }]
+            }
+        },
+        "type": "object",
+        "properties": {
+            "steps": {"type": "array", "items": {"$ref": "#/definitions/step"}}
+        }
+    }"##;
+
+    fn errors(value: &str) -> Vec<String> {
+        let schema: Value = from_str(TEST_SCHEMA).unwrap();
+        let mut errors = Vec::new();
+        check(&schema, &schema, &from_str(value).unwrap(), "$", &mut errors);
+        errors
+    }
+
+    #[test]
+    fn valid_value_has_no_errors() {
+        let steps = r#"{"steps": [{"kind": "split", "size": 2},
+                                  {"kind": "join"}]}"#;
+        assert!(errors(steps).is_empty());
+    }
+
+    #[test]
+    fn ref_reaches_the_definition() {
+        assert_eq!(
+            errors(r#"{"steps": [{"kind": "merge"}, {"size": 0}]}"#),
+            vec![
+                "$.steps[0].kind: \"merge\" is not one of \"split\", \"join\"",
+                "$.steps[1]: missing required field kind",
+                "$.steps[1].size: 0 is less than the minimum 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn if_then_applies_only_when_if_holds() {
+        assert_eq!(
+            errors(r#"{"steps": [{"kind": "split"}]}"#),
+            vec!["$.steps[0]: missing required field size"]
+        );
+        assert!(errors(r#"{"steps": [{"kind": "join"}]}"#).is_empty());
+    }
+
+    #[test]
+    fn additional_properties_false_rejects_unknown_fields() {
+        assert_eq!(
+            errors(r#"{"steps": [{"kind": "join", "extra": 1}], "other": 2}"#),
+            vec!["$.steps[0].extra: unknown field"]
+        );
+    }
+
+    #[test]
+    fn type_mismatch_stops_checking_that_value() {
+        assert_eq!(
+            errors(r#"{"steps": {"kind": "join"}}"#),
+            vec!["$.steps: expected array, found object"]
+        );
+    }
+}
@@ -0,0 +1,141 @@
+use clap;
+use hyper::status::StatusCode;
+use serde_json::Value;
+use std::cmp::min;
+use std::thread;
+use std::time::{Duration, Instant};
+use wdsapi::common::ApiError;
+
+// Polls back off exponentially from one second up to this.
+const MAX_INTERVAL_SECS: u64 = 16;
+
+pub struct Poll {
+    deadline: Option<Instant>,
+    interval: Duration,
+}
+
+impl Poll {
+    // Seconds to keep polling come from --timeout; default is 300.
+    pub fn new(matches: &clap::ArgMatches) -> Poll {
+        Poll::with_default(matches, Some(300))
+    }
+
+    // Keep polling for as long as it takes unless --timeout is given.
+    pub fn unbounded(matches: &clap::ArgMatches) -> Poll {
+        Poll::with_default(matches, None)
+    }
+
+    fn with_default(matches: &clap::ArgMatches, timeout: Option<u64>) -> Poll {
+        let timeout = match matches.value_of("timeout") {
+            Some(timeout) => {
+                Some(timeout.parse().expect("Timeout must be an integer"))
+            }
+            None => timeout,
+        };
+        Poll {
+            deadline: timeout.map(|timeout| {
+                Instant::now() + Duration::from_secs(timeout)
+            }),
+            interval: Duration::from_secs(1),
+        }
+    }
+
+    // A poll with the same deadline that backs off from the start again,
+    // for waiting on several things in turn within one timeout.
+    pub fn restarted(&self) -> Poll {
+        Poll {
+            deadline: self.deadline,
+            interval: Duration::from_secs(1),
+        }
+    }
+
+    // Sleep until the next poll. Returns false, without sleeping, once
+    // the timeout has passed.
+    pub fn next(&mut self) -> bool {
+        let sleep = match self.deadline {
+            Some(deadline) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    return false;
+                }
+                min(self.interval, deadline - now)
+            }
+            None => self.interval,
+        };
+        thread::sleep(sleep);
+        self.interval =
+            min(self.interval * 2, Duration::from_secs(MAX_INTERVAL_SECS));
+        true
+    }
+}
+
+fn is_not_found(e: &ApiError) -> bool {
+    match *e {
+        ApiError::Service(ref se) => se.status_code == StatusCode::NotFound,
+        _ => false,
+    }
+}
+
+// Call `detail` until the service says the resource is Not Found.
+// Returns false when the poll times out first.
+pub fn wait_until_gone<F>(poll: &mut Poll, what: &str, detail: F) -> bool
+where
+    F: Fn() -> Result<Value, ApiError>,
+{
+    println!("Waiting for {} to be deleted", what);
+    loop {
+        match detail() {
+            Err(ref e) if is_not_found(e) => {
+                println!("Deleted {}", what);
+                return true;
+            }
+            Err(e) => {
+                println!("Continuing after {} status check failure {}", what, e)
+            }
+            Ok(_) => (),
+        }
+        if !poll.next() {
+            println!("Timed out waiting for {} to be deleted", what);
+            return false;
+        }
+    }
+}
+
+// Call `detail` until the status it reports is one of `ready`, printing
+// each change of status. Returns false when the poll times out first.
+pub fn wait_for_status<F>(
+    poll: &mut Poll,
+    what: &str,
+    ready: &[&str],
+    detail: F,
+) -> bool
+where
+    F: Fn() -> Result<Value, ApiError>,
+{
+    let mut last_status = String::new();
+    loop {
+        match detail() {
+            Ok(response) => {
+                let status = response["status"].as_str().unwrap_or("unknown");
+                if status != last_status {
+                    println!("{} is {}", what, status);
+                    last_status = status.to_string();
+                }
+                if ready.contains(&status) {
+                    return true;
+                }
+            }
+            Err(e) => {
+                println!("Continuing after {} status check failure {}", what, e)
+            }
+        }
+        if !poll.next() {
+            println!(
+                "Timed out waiting for {} to become {}",
+                what,
+                ready.join(" or ")
+            );
+            return false;
+        }
+    }
+}